@@ -0,0 +1,151 @@
+use crate::ICOSAHEDRON_TRIANGLES;
+
+/// Finds the triangles sharing an edge with `triangle_index` in an icosphere of the given binning depth.
+///
+/// Edge `i` goes from corner `i` to corner `(i + 1) % 3` of the triangle, in the same order as
+/// [`crate::Icosphere::triangle`], and the triangle across it is at index `i` of the result.
+///
+/// Like [`crate::locate::locate_triangle`], this doesn't need an icosphere to exist: the neighbor is
+/// found by walking up the triangle hierarchy until the edge is inside a common ancestor, then back
+/// down on the other side, so this runs in O(binning depth).
+pub fn edge_neighbors(triangle_index: usize, binning_depth: usize) -> [usize; 3] {
+    [0, 1, 2].map(|edge| edge_neighbor(triangle_index, edge, binning_depth).0)
+}
+
+/// The triangle across edge `edge` of `triangle_index` (see [`edge_neighbors`]), along with the index
+/// of the same edge in that triangle. The edge goes in the opposite direction in the neighbor.
+pub fn edge_neighbor(triangle_index: usize, edge: usize, binning_depth: usize) -> (usize, usize) {
+    if binning_depth == 0 {
+        return base_edge_neighbor(triangle_index, edge);
+    }
+
+    let parent_index = triangle_index / 4;
+    let child = triangle_index % 4;
+
+    // Children of [a, b, c] are [a, d, f], [b, e, d], [c, f, e] and [d, e, f]. Edge 1 of each corner
+    // child borders the center child, and the other two are halves of the parent's edges.
+    match (child, edge) {
+        (3, edge) => (parent_index * 4 + (edge + 1) % 3, 1),
+        (child, 1) => (parent_index * 4 + 3, (child + 2) % 3),
+        (child, 0) => {
+            // The first half of the parent's edge `child`, which is the second half in the neighbor
+            let (neighbor, neighbor_edge) = edge_neighbor(parent_index, child, binning_depth - 1);
+
+            (neighbor * 4 + (neighbor_edge + 1) % 3, 2)
+        }
+        (child, _) => {
+            // The second half of the parent's edge `(child + 2) % 3`, which is the first half in the
+            // neighbor
+            let (neighbor, neighbor_edge) =
+                edge_neighbor(parent_index, (child + 2) % 3, binning_depth - 1);
+
+            (neighbor * 4 + neighbor_edge, 0)
+        }
+    }
+}
+
+/// Finds the triangles that share only a vertex with `triangle_index`, without sharing an edge. Most
+/// triangles have nine of these, and triangles touching one of the twelve vertices of the regular
+/// icosahedron have fewer.
+///
+/// Triangles are ordered counter-clockwise around each corner, starting from corner 0.
+pub fn vertex_neighbors(triangle_index: usize, binning_depth: usize) -> Vec<usize> {
+    let edge_neighbors = edge_neighbors(triangle_index, binning_depth);
+    let mut neighbors = Vec::with_capacity(9);
+
+    for corner in 0..3 {
+        // Corner `corner` is where edge `corner` starts, so crossing that edge and then the edge
+        // starting at the same vertex in each neighbor walks around it
+        let (mut current, mut edge) = edge_neighbor(triangle_index, corner, binning_depth);
+
+        loop {
+            (current, edge) = edge_neighbor(current, (edge + 1) % 3, binning_depth);
+
+            if current == triangle_index || edge_neighbors.contains(&current) {
+                break;
+            }
+
+            neighbors.push(current);
+        }
+    }
+
+    neighbors
+}
+
+/// Same as [`edge_neighbor`], for a face of the regular icosahedron.
+fn base_edge_neighbor(face: usize, edge: usize) -> (usize, usize) {
+    let triangle = ICOSAHEDRON_TRIANGLES[face];
+    let (from, to) = (triangle[edge], triangle[(edge + 1) % 3]);
+
+    ICOSAHEDRON_TRIANGLES
+        .iter()
+        .enumerate()
+        .find_map(|(neighbor, other)| {
+            (0..3)
+                .find(|&i| other[i] == to && other[(i + 1) % 3] == from)
+                .map(|neighbor_edge| (neighbor, neighbor_edge))
+        })
+        .expect("The regular icosahedron is closed")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{HashMap, HashSet};
+
+    use glam::Vec3;
+
+    use super::*;
+    use crate::StaticIcosphere;
+
+    #[test]
+    fn edge_neighbors_match_mesh() {
+        for binning_depth in 0..4 {
+            let ico = StaticIcosphere::<Vec3>::nth(binning_depth);
+
+            // Every directed edge of the mesh, and the triangle and edge index it belongs to
+            let edges: HashMap<(u32, u32), (usize, usize)> = ico
+                .triangles
+                .iter()
+                .enumerate()
+                .flat_map(|(triangle_index, triangle)| {
+                    (0..3).map(move |edge| {
+                        let key = (triangle[edge], triangle[(edge + 1) % 3]);
+                        (key, (triangle_index, edge))
+                    })
+                })
+                .collect();
+
+            for (triangle_index, triangle) in ico.triangles.iter().enumerate() {
+                for edge in 0..3 {
+                    let reversed = (triangle[(edge + 1) % 3], triangle[edge]);
+
+                    assert_eq!(
+                        edge_neighbor(triangle_index, edge, binning_depth),
+                        edges[&reversed]
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_neighbors_match_mesh() {
+        for binning_depth in 0..3 {
+            let ico = StaticIcosphere::<Vec3>::nth(binning_depth);
+
+            for (triangle_index, triangle) in ico.triangles.iter().enumerate() {
+                let expected: HashSet<usize> = ico
+                    .triangles
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, other)| other.iter().filter(|i| triangle.contains(i)).count() == 1)
+                    .map(|(other_index, _)| other_index)
+                    .collect();
+
+                let found = vertex_neighbors(triangle_index, binning_depth);
+                assert_eq!(found.len(), expected.len());
+                assert_eq!(found.into_iter().collect::<HashSet<_>>(), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::{collections::HashMap, f32::consts::PI};
+
+use bytemuck::{Pod, Zeroable};
+use glam::{Vec2, Vec3};
+
+use crate::{Icosphere, IcosphereVertex, export::CompactMesh};
+
+/// Horizontal distance from the y axis below which a vertex is considered to be on a pole.
+const POLE_EPSILON: f32 = 1e-6;
+
+/// An icosphere vertex with everything needed to render a textured sphere, laid out so that it can be
+/// uploaded to a vertex buffer directly.
+///
+/// The y axis points to the north pole. Texture coordinates are equirectangular, with u increasing
+/// eastwards and v increasing southwards. See [`equirectangular_uv`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Pod, Zeroable)]
+pub struct RenderVertex {
+    pub position: [f32; 3],
+    pub normal: [f32; 3],
+
+    /// Points east, with the handedness of the bitangent in `w`, such that the bitangent
+    /// `normal.cross(tangent.xyz) * tangent.w` points south.
+    pub tangent: [f32; 4],
+    pub uv: [f32; 2],
+}
+
+impl RenderVertex {
+    /// Same as [`IcosphereVertex::from_position`], but with a given u coordinate, for vertices where
+    /// it's ambiguous (on the seam or on a pole).
+    fn with_u(position: Vec3, u: f32) -> Self {
+        let longitude = (u - 0.5) * 2.0 * PI;
+        let tangent = Vec3::new(-longitude.sin(), 0.0, longitude.cos());
+
+        Self {
+            position: position.to_array(),
+            normal: position.normalize().to_array(),
+            tangent: tangent.extend(1.0).to_array(),
+            uv: [u, equirectangular_uv(position).y],
+        }
+    }
+}
+
+impl IcosphereVertex for RenderVertex {
+    fn position(&self) -> Vec3 {
+        Vec3::from(self.position)
+    }
+
+    fn from_position(position: Vec3, _binning_depth: usize) -> Self {
+        Self::with_u(position, equirectangular_uv(position).x)
+    }
+}
+
+/// Equirectangular texture coordinates of a position. Both coordinates are in `0.0..=1.0`, with u = 0.5
+/// facing +x, and v = 0 at the north pole (+y).
+pub fn equirectangular_uv(position: Vec3) -> Vec2 {
+    let direction = position.normalize();
+
+    Vec2::new(
+        0.5 + direction.z.atan2(direction.x) / (2.0 * PI),
+        0.5 - direction.y.clamp(-1.0, 1.0).asin() / PI,
+    )
+}
+
+/// A mesh that can be rendered with an equirectangular texture without distortion at the seam or the
+/// poles.
+///
+/// Triangles crossing the antimeridian (where u wraps from 1 back to 0) use copies of their vertices
+/// with u shifted up by one, so the texture must be sampled with repeat wrapping in u. Vertices on a
+/// pole get a copy for every triangle around them, with u in the middle of the triangle's other
+/// vertices.
+#[derive(Debug, Clone)]
+pub struct RenderMesh {
+    pub vertices: Vec<RenderVertex>,
+    pub indices: Vec<u32>,
+
+    /// For each vertex, the index in [`Icosphere::vertices`] it was created from. Copies made for the
+    /// seam and the poles share the index of the original.
+    pub source_vertices: Vec<usize>,
+
+    /// For each vertex, its position on the previous binning depth (see
+    /// [`Icosphere::parent_positions`]), if the mesh was built with
+    /// [`Self::new_with_parent_positions`]. Meant to be uploaded as a second vertex buffer, so a
+    /// vertex shader can blend between the two positions when a chunk changes level. That's only
+    /// exact if the levels are consecutive binning depths, with a `binning_depth_step` of 1 in
+    /// [`crate::levels::IcosphereLevels`].
+    pub parent_positions: Option<Vec<[f32; 3]>>,
+}
+
+impl RenderMesh {
+    /// Builds a mesh from every generated triangle of the icosphere.
+    pub fn new<T: IcosphereVertex, S: Icosphere<T>>(ico: &S) -> Self {
+        let mesh = CompactMesh::new(ico);
+        let positions = mesh.positions(ico.vertices());
+
+        let mut vertices: Vec<RenderVertex> = positions
+            .iter()
+            .map(|&position| RenderVertex::from_position(position, ico.binning_depth()))
+            .collect();
+        let mut source_vertices = mesh.vertices.clone();
+        let mut indices = Vec::with_capacity(mesh.indices.len() * 3);
+
+        // Copies of vertices on the seam, by the index of the original
+        let mut seam_copies: HashMap<u32, u32> = HashMap::new();
+
+        for &triangle in &mesh.indices {
+            let mut triangle = triangle;
+            let poles = triangle.map(|i| {
+                let position = positions[i as usize];
+                Vec2::new(position.x, position.z).length() < POLE_EPSILON
+            });
+
+            let us = triangle.map(|i| vertices[i as usize].uv[0]);
+            let (min_u, max_u) = (0..3)
+                .filter(|&k| !poles[k])
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), k| {
+                    (min.min(us[k]), max.max(us[k]))
+                });
+
+            if max_u - min_u > 0.5 {
+                for k in (0..3).filter(|&k| !poles[k] && us[k] < 0.5) {
+                    let original = triangle[k];
+
+                    triangle[k] = *seam_copies.entry(original).or_insert_with(|| {
+                        let position = positions[original as usize];
+
+                        vertices.push(RenderVertex::with_u(position, us[k] + 1.0));
+                        source_vertices.push(source_vertices[original as usize]);
+
+                        vertices.len() as u32 - 1
+                    });
+                }
+            }
+
+            for k in (0..3).filter(|&k| poles[k]) {
+                let original = triangle[k];
+                let position = positions[original as usize];
+
+                let u = (vertices[triangle[(k + 1) % 3] as usize].uv[0]
+                    + vertices[triangle[(k + 2) % 3] as usize].uv[0])
+                    / 2.0;
+
+                vertices.push(RenderVertex::with_u(position, u));
+                source_vertices.push(source_vertices[original as usize]);
+
+                triangle[k] = vertices.len() as u32 - 1;
+            }
+
+            indices.extend(triangle);
+        }
+
+        // Displaced surfaces have their own normals, so the tangents are made perpendicular to them
+        for (vertex, &source) in vertices.iter_mut().zip(&source_vertices) {
+            let normal = ico.vertices()[source].surface_normal();
+            let tangent = Vec3::from_slice(&vertex.tangent[..3]);
+
+            vertex.normal = normal.to_array();
+            vertex.tangent = (tangent - normal * normal.dot(tangent))
+                .normalize_or(tangent)
+                .extend(1.0)
+                .to_array();
+        }
+
+        Self {
+            vertices,
+            indices,
+            source_vertices,
+            parent_positions: None,
+        }
+    }
+
+    /// Same as [`Self::new`], but also fills [`Self::parent_positions`].
+    pub fn new_with_parent_positions<T: IcosphereVertex, S: Icosphere<T>>(ico: &S) -> Self {
+        let mut mesh = Self::new(ico);
+        let parent_positions = ico.parent_positions();
+
+        mesh.parent_positions = Some(
+            mesh.source_vertices
+                .iter()
+                .map(|&vertex| parent_positions[vertex].to_array())
+                .collect(),
+        );
+
+        mesh
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StaticIcosphere;
+
+    fn is_pole(position: Vec3) -> bool {
+        Vec2::new(position.x, position.z).length() < POLE_EPSILON
+    }
+
+    fn render_triangles(mesh: &RenderMesh) -> impl Iterator<Item = [RenderVertex; 3]> + '_ {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|triangle| [0, 1, 2].map(|k| mesh.vertices[triangle[k] as usize]))
+    }
+
+    #[test]
+    fn triangles_keep_their_corners() {
+        let ico = StaticIcosphere::<Vec3>::nth(2);
+        let mesh = RenderMesh::new(&ico);
+
+        assert_eq!(mesh.indices.len(), 3 * ico.triangles.len());
+        assert_eq!(mesh.source_vertices.len(), mesh.vertices.len());
+
+        for (triangle, render_triangle) in ico.triangles.iter().zip(render_triangles(&mesh)) {
+            for (&vertex_index, vertex) in triangle.iter().zip(render_triangle) {
+                assert_eq!(
+                    Vec3::from(vertex.position),
+                    ico.vertices[vertex_index as usize]
+                );
+            }
+        }
+
+        for (vertex, &source) in mesh.vertices.iter().zip(&mesh.source_vertices) {
+            assert_eq!(Vec3::from(vertex.position), ico.vertices[source]);
+        }
+    }
+
+    #[test]
+    fn no_triangle_wraps_around_the_seam() {
+        let mesh = RenderMesh::new(&StaticIcosphere::<Vec3>::nth(3));
+        let mut seam_copies = 0;
+
+        for vertex in &mesh.vertices {
+            let position = Vec3::from(vertex.position);
+            let expected = equirectangular_uv(position);
+
+            assert_eq!(vertex.uv[1], expected.y);
+
+            if !is_pole(position) && vertex.uv[0] != expected.x {
+                assert_eq!(vertex.uv[0], expected.x + 1.0);
+                seam_copies += 1;
+            }
+        }
+
+        assert!(seam_copies > 0);
+
+        for triangle in render_triangles(&mesh) {
+            let us: Vec<f32> = triangle
+                .iter()
+                .filter(|vertex| !is_pole(Vec3::from(vertex.position)))
+                .map(|vertex| vertex.uv[0])
+                .collect();
+
+            let (min_u, max_u) = us
+                .iter()
+                .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), &u| {
+                    (min.min(u), max.max(u))
+                });
+            assert!(max_u - min_u <= 0.5);
+        }
+    }
+
+    #[test]
+    fn poles_have_a_copy_for_every_triangle() {
+        let mesh = RenderMesh::new(&StaticIcosphere::<Vec3>::nth(2));
+
+        let pole_indices = mesh
+            .indices
+            .iter()
+            .filter(|&&i| is_pole(Vec3::from(mesh.vertices[i as usize].position)));
+
+        // Both poles are midpoints of the regular icosahedron's edges, with six triangles around them
+        assert_eq!(pole_indices.count(), 12);
+
+        for triangle in mesh.indices.chunks_exact(3) {
+            for k in 0..3 {
+                let vertex = mesh.vertices[triangle[k] as usize];
+
+                if !is_pole(Vec3::from(vertex.position)) {
+                    continue;
+                }
+
+                // Only used by this triangle, halfway between the others in u
+                assert_eq!(
+                    mesh.indices.iter().filter(|&&i| i == triangle[k]).count(),
+                    1
+                );
+
+                let others = [(k + 1) % 3, (k + 2) % 3]
+                    .map(|other| mesh.vertices[triangle[other] as usize].uv[0]);
+                assert_eq!(vertex.uv[0], (others[0] + others[1]) / 2.0);
+                assert!(vertex.uv[1] == 0.0 || vertex.uv[1] == 1.0);
+            }
+        }
+    }
+
+    #[test]
+    fn tangents_point_east() {
+        let mesh = RenderMesh::new(&StaticIcosphere::<Vec3>::nth(2));
+
+        for vertex in &mesh.vertices {
+            let position = Vec3::from(vertex.position);
+            let normal = Vec3::from(vertex.normal);
+            let tangent = Vec3::from_slice(&vertex.tangent[..3]);
+
+            assert!(normal.distance(position) < 1e-6);
+            assert!((tangent.length() - 1.0).abs() < 1e-5);
+            assert!(tangent.dot(normal).abs() < 1e-5);
+
+            if !is_pole(position) {
+                let east = position.cross(Vec3::Y).normalize();
+                assert!(tangent.dot(east) > 0.9999);
+            }
+        }
+    }
+}
@@ -0,0 +1,499 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    io::{self, Write},
+    mem,
+};
+
+use bytemuck::{Pod, Zeroable};
+
+use crate::{
+    IcosphereVertex, MAX_BINNING_DEPTH, StaticIcosphere, midpoint::MidpointRule,
+    neighbors_from_triangles, triangle_count, vertex_count,
+};
+
+/// The first eight bytes of every cache.
+pub const CACHE_MAGIC: [u8; 8] = *b"ICOSPHRE";
+
+/// Incremented whenever the layout of the cache changes. Caches of other versions are rejected.
+pub const CACHE_VERSION: u32 = 1;
+
+/// Alignment of the vertex and triangle sections, relative to the start of the cache. If the cache is
+/// memory-mapped, the sections can be used in place.
+const CACHE_ALIGNMENT: usize = 16;
+
+/// The start of a cache, followed by the vertices and then the triangles, in native byte order.
+///
+/// Both sections are raw arrays that can be uploaded directly as vertex and index buffers. Neighbors
+/// aren't stored, and are reconstructed from the triangles when loading a [`StaticIcosphere`].
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Pod, Zeroable)]
+pub struct CacheHeader {
+    pub magic: [u8; 8],
+    pub version: u32,
+    pub binning_depth: u32,
+
+    /// `size_of::<T>()` of the vertex type used when writing the cache.
+    pub vertex_size: u32,
+    pub vertex_count: u32,
+    pub triangle_count: u32,
+
+    /// See [`MidpointRule::to_u32`].
+    pub midpoint_rule: u32,
+
+    /// Byte offset of the vertex section from the start of the cache.
+    pub vertex_offset: u64,
+
+    /// Byte offset of the triangle section from the start of the cache.
+    pub triangle_offset: u64,
+}
+
+/// Why a cache couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CacheError {
+    /// The bytes don't start with [`CACHE_MAGIC`].
+    InvalidMagic,
+
+    /// The cache was written with a different [`CACHE_VERSION`].
+    UnsupportedVersion(u32),
+
+    /// The cache was written with a vertex type of a different size.
+    VertexSizeMismatch { expected: usize, found: usize },
+
+    /// The cache was written with a midpoint rule this version doesn't know about.
+    UnknownMidpointRule(u32),
+
+    /// The binning depth is larger than [`MAX_BINNING_DEPTH`].
+    BinningDepthTooLarge(u32),
+
+    /// The number of triangles isn't the triangle count of the binning depth.
+    TriangleCountMismatch { expected: usize, found: usize },
+
+    /// The number of vertices isn't the vertex count of the binning depth.
+    VertexCountMismatch { expected: usize, found: usize },
+
+    /// A triangle refers to a vertex past the end of the vertex section.
+    VertexIndexOutOfRange {
+        triangle_index: usize,
+        vertex_index: u32,
+    },
+
+    /// The bytes end before the sections described by the header do.
+    Truncated,
+
+    /// The sections aren't aligned in memory, so they can't be used in place. Reading the cache into
+    /// a [`StaticIcosphere`] doesn't have this restriction.
+    Misaligned,
+}
+
+impl fmt::Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CacheError::InvalidMagic => write!(f, "not an icosphere cache"),
+            CacheError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported icosphere cache version {version}, expected {CACHE_VERSION}"
+            ),
+            CacheError::VertexSizeMismatch { expected, found } => write!(
+                f,
+                "icosphere cache has vertices of {found} bytes, expected {expected}"
+            ),
+            CacheError::UnknownMidpointRule(midpoint_rule) => {
+                write!(
+                    f,
+                    "unknown midpoint rule {midpoint_rule} in icosphere cache"
+                )
+            }
+            CacheError::BinningDepthTooLarge(binning_depth) => write!(
+                f,
+                "icosphere cache has binning depth {binning_depth}, the maximum is {MAX_BINNING_DEPTH}"
+            ),
+            CacheError::TriangleCountMismatch { expected, found } => write!(
+                f,
+                "icosphere cache has {found} triangles, expected {expected}"
+            ),
+            CacheError::VertexCountMismatch { expected, found } => write!(
+                f,
+                "icosphere cache has {found} vertices, expected {expected}"
+            ),
+            CacheError::VertexIndexOutOfRange {
+                triangle_index,
+                vertex_index,
+            } => write!(
+                f,
+                "triangle {triangle_index} of icosphere cache refers to missing vertex {vertex_index}"
+            ),
+            CacheError::Truncated => write!(f, "icosphere cache is truncated"),
+            CacheError::Misaligned => write!(f, "icosphere cache is not aligned in memory"),
+        }
+    }
+}
+
+impl std::error::Error for CacheError {}
+
+/// A cache borrowed in place, e.g. from a memory-mapped file, without copying or reconstructing
+/// anything.
+#[derive(Debug, Clone, Copy)]
+pub struct CacheView<'a, T: Pod> {
+    pub header: CacheHeader,
+
+    /// Same as [`StaticIcosphere::vertices`].
+    pub vertices: &'a [T],
+
+    /// Same as [`StaticIcosphere::triangles`].
+    pub triangles: &'a [[u32; 3]],
+}
+
+impl<'a, T: Pod> CacheView<'a, T> {
+    /// Validates the header and borrows both sections. The bytes must be aligned to the alignment of
+    /// `T` and of `u32`, which is always the case for memory-mapped files.
+    pub fn new(bytes: &'a [u8]) -> Result<Self, CacheError> {
+        let header = read_header::<T>(bytes)?;
+        let (vertex_bytes, triangle_bytes) = sections::<T>(&header, bytes)?;
+
+        let vertices =
+            bytemuck::try_cast_slice(vertex_bytes).map_err(|_| CacheError::Misaligned)?;
+        let triangles =
+            bytemuck::try_cast_slice(triangle_bytes).map_err(|_| CacheError::Misaligned)?;
+
+        validate_triangles(triangles, vertices.len())?;
+
+        Ok(Self {
+            header,
+            vertices,
+            triangles,
+        })
+    }
+
+    /// The triangles flattened into an index buffer.
+    pub fn indices(&self) -> &'a [u32] {
+        bytemuck::cast_slice(self.triangles)
+    }
+}
+
+impl<T: IcosphereVertex + Pod> StaticIcosphere<T> {
+    /// Encodes this icosphere into the cache format. See [`CacheHeader`].
+    ///
+    /// Panics if the icosphere is too large for the format, from binning depth 14 on, when there are
+    /// more triangles than a `u32` can count.
+    pub fn to_cache(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        self.write_cache(&mut bytes)
+            .unwrap_or_else(|error| panic!("{error}"));
+
+        bytes
+    }
+
+    /// Same as [`Self::to_cache`], but writes directly to `writer`. Returns an error of kind
+    /// [`io::ErrorKind::InvalidInput`] before writing anything if the icosphere is too large.
+    pub fn write_cache<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let vertex_bytes: &[u8] = bytemuck::cast_slice(&self.vertices);
+        let triangle_bytes: &[u8] = bytemuck::cast_slice(&self.triangles);
+
+        let vertex_offset = mem::size_of::<CacheHeader>().next_multiple_of(CACHE_ALIGNMENT);
+        let triangle_offset =
+            (vertex_offset + vertex_bytes.len()).next_multiple_of(CACHE_ALIGNMENT);
+
+        let header = CacheHeader {
+            magic: CACHE_MAGIC,
+            version: CACHE_VERSION,
+            binning_depth: header_field(self.binning_depth, "binning depth")?,
+            vertex_size: mem::size_of::<T>() as u32,
+            vertex_count: header_field(self.vertices.len(), "vertex count")?,
+            triangle_count: header_field(self.triangles.len(), "triangle count")?,
+            midpoint_rule: self.midpoint_rule.to_u32(),
+            vertex_offset: vertex_offset as u64,
+            triangle_offset: triangle_offset as u64,
+        };
+
+        let padding = [0u8; CACHE_ALIGNMENT];
+
+        writer.write_all(bytemuck::bytes_of(&header))?;
+        writer.write_all(&padding[..vertex_offset - mem::size_of::<CacheHeader>()])?;
+        writer.write_all(vertex_bytes)?;
+        writer.write_all(&padding[..triangle_offset - vertex_offset - vertex_bytes.len()])?;
+        writer.write_all(triangle_bytes)?;
+
+        writer.flush()
+    }
+
+    /// Decodes an icosphere from the cache format, reconstructing the neighbors. Unlike [`CacheView`],
+    /// the bytes may have any alignment.
+    pub fn from_cache(bytes: &[u8]) -> Result<Self, CacheError> {
+        let header = read_header::<T>(bytes)?;
+        let (vertex_bytes, triangle_bytes) = sections::<T>(&header, bytes)?;
+
+        let vertices: Vec<T> = vertex_bytes
+            .chunks_exact(mem::size_of::<T>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+        let triangles: Vec<[u32; 3]> = triangle_bytes
+            .chunks_exact(mem::size_of::<[u32; 3]>())
+            .map(bytemuck::pod_read_unaligned)
+            .collect();
+
+        validate_triangles(&triangles, vertices.len())?;
+
+        let neighbors = neighbors_from_triangles(&triangles);
+        let binning_depth = header.binning_depth as usize;
+        let midpoint_rule = MidpointRule::from_u32(header.midpoint_rule)
+            .ok_or(CacheError::UnknownMidpointRule(header.midpoint_rule))?;
+
+        let mut midpoints = HashMap::new();
+
+        // Children are laid out as [a, d, f], [b, e, d], [c, f, e], [d, e, f], where d, e and f are
+        // the midpoints of the parent's edges
+        if binning_depth > 0 {
+            for children in triangles.chunks_exact(4) {
+                let [a, d, f] = children[0].map(|i| i as usize);
+                let [b, e, _] = children[1].map(|i| i as usize);
+                let c = children[2][0] as usize;
+
+                for ((i, j), midpoint) in [((a, b), d), ((b, c), e), ((c, a), f)] {
+                    let key = if i > j { (j, i) } else { (i, j) };
+                    midpoints.insert(key, midpoint);
+                }
+            }
+        }
+
+        Ok(Self {
+            vertices,
+            triangles,
+            neighbors,
+            binning_depth,
+            midpoint_rule,
+            midpoints,
+        })
+    }
+}
+
+/// Reads and validates the header at the start of `bytes`, including that it describes a complete
+/// icosphere of its binning depth.
+/// Converts a value to the size it has in [`CacheHeader`], or returns an error if it doesn't fit.
+fn header_field(value: usize, name: &str) -> io::Result<u32> {
+    u32::try_from(value).map_err(|_| {
+        io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("{name} {value} is too large for an icosphere cache"),
+        )
+    })
+}
+
+fn read_header<T: Pod>(bytes: &[u8]) -> Result<CacheHeader, CacheError> {
+    let header_bytes = bytes
+        .get(..mem::size_of::<CacheHeader>())
+        .ok_or(CacheError::Truncated)?;
+    let header: CacheHeader = bytemuck::pod_read_unaligned(header_bytes);
+
+    if header.magic != CACHE_MAGIC {
+        return Err(CacheError::InvalidMagic);
+    }
+
+    if header.version != CACHE_VERSION {
+        return Err(CacheError::UnsupportedVersion(header.version));
+    }
+
+    if header.vertex_size as usize != mem::size_of::<T>() {
+        return Err(CacheError::VertexSizeMismatch {
+            expected: mem::size_of::<T>(),
+            found: header.vertex_size as usize,
+        });
+    }
+
+    let binning_depth = header.binning_depth as usize;
+
+    if binning_depth > MAX_BINNING_DEPTH {
+        return Err(CacheError::BinningDepthTooLarge(header.binning_depth));
+    }
+
+    if header.triangle_count as usize != triangle_count(binning_depth) {
+        return Err(CacheError::TriangleCountMismatch {
+            expected: triangle_count(binning_depth),
+            found: header.triangle_count as usize,
+        });
+    }
+
+    if header.vertex_count as usize != vertex_count(binning_depth) {
+        return Err(CacheError::VertexCountMismatch {
+            expected: vertex_count(binning_depth),
+            found: header.vertex_count as usize,
+        });
+    }
+
+    Ok(header)
+}
+
+/// Checks that every triangle only refers to existing vertices.
+fn validate_triangles(triangles: &[[u32; 3]], vertex_count: usize) -> Result<(), CacheError> {
+    for (triangle_index, triangle) in triangles.iter().enumerate() {
+        if let Some(&vertex_index) = triangle
+            .iter()
+            .find(|&&vertex_index| vertex_index as usize >= vertex_count)
+        {
+            return Err(CacheError::VertexIndexOutOfRange {
+                triangle_index,
+                vertex_index,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+/// Splits `bytes` into the vertex and triangle sections described by the header.
+fn sections<'a, T: Pod>(
+    header: &CacheHeader,
+    bytes: &'a [u8],
+) -> Result<(&'a [u8], &'a [u8]), CacheError> {
+    let section = |offset: u64, length: usize| {
+        let start = usize::try_from(offset).map_err(|_| CacheError::Truncated)?;
+        let end = start.checked_add(length).ok_or(CacheError::Truncated)?;
+
+        bytes.get(start..end).ok_or(CacheError::Truncated)
+    };
+
+    let vertices = section(
+        header.vertex_offset,
+        header.vertex_count as usize * mem::size_of::<T>(),
+    )?;
+    let triangles = section(
+        header.triangle_offset,
+        header.triangle_count as usize * mem::size_of::<[u32; 3]>(),
+    )?;
+
+    Ok((vertices, triangles))
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    fn cache() -> Vec<u8> {
+        StaticIcosphere::<Vec3>::nth(2).to_cache()
+    }
+
+    fn with_header(bytes: &[u8], edit: impl FnOnce(&mut CacheHeader)) -> Vec<u8> {
+        let mut bytes = bytes.to_vec();
+        let mut header: CacheHeader =
+            bytemuck::pod_read_unaligned(&bytes[..mem::size_of::<CacheHeader>()]);
+        edit(&mut header);
+        bytes[..mem::size_of::<CacheHeader>()].copy_from_slice(bytemuck::bytes_of(&header));
+
+        bytes
+    }
+
+    /// Reads the bytes through a [`CacheView`], copied to a buffer aligned for its sections.
+    fn view(bytes: &[u8]) -> Result<(Vec<Vec3>, Vec<[u32; 3]>), CacheError> {
+        let mut aligned = vec![0u32; bytes.len().div_ceil(4)];
+        bytemuck::cast_slice_mut::<u32, u8>(&mut aligned)[..bytes.len()].copy_from_slice(bytes);
+
+        let view = CacheView::<Vec3>::new(&bytemuck::cast_slice(&aligned)[..bytes.len()])?;
+        Ok((view.vertices.to_vec(), view.triangles.to_vec()))
+    }
+
+    #[test]
+    fn round_trip() {
+        let ico = StaticIcosphere::<Vec3>::nth(2);
+        let bytes = ico.to_cache();
+
+        let read = StaticIcosphere::<Vec3>::from_cache(&bytes).unwrap();
+        assert_eq!(read.vertices, ico.vertices);
+        assert_eq!(read.triangles, ico.triangles);
+        assert_eq!(read.neighbors, ico.neighbors);
+        assert_eq!(read.midpoints, ico.midpoints);
+
+        assert_eq!(view(&bytes).unwrap(), (ico.vertices, ico.triangles));
+    }
+
+    #[test]
+    fn values_too_large_for_the_header() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(0);
+        ico.binning_depth = u32::MAX as usize + 1;
+
+        let mut bytes = Vec::new();
+        let error = ico.write_cache(&mut bytes).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(bytes.is_empty());
+
+        assert!(header_field(u32::MAX as usize, "triangle count").is_ok());
+        assert!(header_field(u32::MAX as usize + 1, "triangle count").is_err());
+    }
+
+    #[test]
+    fn truncated() {
+        let bytes = cache();
+
+        for length in [0, mem::size_of::<CacheHeader>() - 1, 100, bytes.len() - 1] {
+            let bytes = &bytes[..length];
+            assert_eq!(
+                StaticIcosphere::<Vec3>::from_cache(bytes).err(),
+                Some(CacheError::Truncated)
+            );
+            assert_eq!(view(bytes).err(), Some(CacheError::Truncated));
+        }
+    }
+
+    #[test]
+    fn counts_must_match_binning_depth() {
+        let bytes = cache();
+        let cases = [
+            (
+                with_header(&bytes, |header| header.triangle_count -= 1),
+                CacheError::TriangleCountMismatch {
+                    expected: 320,
+                    found: 319,
+                },
+            ),
+            (
+                with_header(&bytes, |header| header.vertex_count -= 1),
+                CacheError::VertexCountMismatch {
+                    expected: 162,
+                    found: 161,
+                },
+            ),
+            (
+                with_header(&bytes, |header| header.binning_depth = 3),
+                CacheError::TriangleCountMismatch {
+                    expected: 1280,
+                    found: 320,
+                },
+            ),
+            (
+                with_header(&bytes, |header| header.binning_depth = u32::MAX),
+                CacheError::BinningDepthTooLarge(u32::MAX),
+            ),
+        ];
+
+        for (bytes, error) in cases {
+            assert_eq!(
+                StaticIcosphere::<Vec3>::from_cache(&bytes).err(),
+                Some(error.clone())
+            );
+            assert_eq!(view(&bytes).err(), Some(error));
+        }
+    }
+
+    #[test]
+    fn vertex_index_out_of_range() {
+        let mut bytes = cache();
+        let header: CacheHeader =
+            bytemuck::pod_read_unaligned(&bytes[..mem::size_of::<CacheHeader>()]);
+
+        // Second corner of the second triangle
+        let offset = header.triangle_offset as usize + 4 * mem::size_of::<u32>();
+        bytes[offset..offset + 4].copy_from_slice(&162u32.to_ne_bytes());
+
+        let error = CacheError::VertexIndexOutOfRange {
+            triangle_index: 1,
+            vertex_index: 162,
+        };
+        assert_eq!(
+            StaticIcosphere::<Vec3>::from_cache(&bytes).err(),
+            Some(error.clone())
+        );
+        assert_eq!(view(&bytes).err(), Some(error));
+    }
+}
@@ -0,0 +1,399 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::Vec3;
+
+use crate::{
+    ICOSAHEDRON_TRIANGLES, Icosphere, IcosphereVertex, MAX_BINNING_DEPTH, StaticIcosphere,
+    adjacency, error::IcosphereError, icosahedron_positions, locate, midpoint::MidpointRule,
+    triangle_count, vertex_count,
+};
+
+/// Canonical IDs of the corners of a triangle, in the same order as [`Icosphere::triangle`].
+///
+/// Canonical IDs number vertices by where they are in the triangle hierarchy, so they don't depend
+/// on the order triangles were generated in, or on whether the icosphere is static or sparse:
+///
+/// - The 12 vertices of the regular icosahedron keep their indices.
+/// - Every vertex created by subdividing from binning depth `d` is numbered `vertex_count(d)` plus
+///   the index of the edge it splits. The edges at `d` are numbered with the three edges inside every
+///   triangle at `d - 1` first (the edges of its center child, so `3 * parent_index + edge`), then
+///   the two halves of every edge at `d - 1`, with the half touching the lower canonical ID first.
+///   The edges of the regular icosahedron are numbered in order of their sorted vertex indices.
+///
+/// A vertex keeps its ID at every deeper binning depth, and the IDs at a binning depth are exactly
+/// `0..vertex_count(binning_depth)`.
+pub fn canonical_triangle_vertices(triangle_index: usize, binning_depth: usize) -> [usize; 3] {
+    canonical_triangle(triangle_index, binning_depth).0
+}
+
+/// The binning depth a vertex with the given canonical ID first appears at.
+///
+/// Panics if no vertex has the ID, even at [`MAX_BINNING_DEPTH`]. See [`try_canonical_vertex_depth`].
+pub fn canonical_vertex_depth(canonical_id: usize) -> usize {
+    try_canonical_vertex_depth(canonical_id).unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Same as [`canonical_vertex_depth`], but returns an error instead of panicking if no vertex has the
+/// ID.
+pub fn try_canonical_vertex_depth(canonical_id: usize) -> Result<usize, IcosphereError> {
+    (0..=MAX_BINNING_DEPTH)
+        .find(|&binning_depth| canonical_id < vertex_count(binning_depth))
+        .ok_or(IcosphereError::CanonicalIdOutOfRange {
+            canonical_id,
+            vertex_count: vertex_count(MAX_BINNING_DEPTH),
+        })
+}
+
+/// The position of the vertex with the given canonical ID, in an icosphere whose vertices were placed
+/// with the given rule. A vertex doesn't move when subdividing further, so this doesn't depend on the
+/// binning depth.
+///
+/// This doesn't need an icosphere to exist: the edge the vertex splits is traced back up the triangle
+/// hierarchy to find a triangle it's inside, whose corners are then computed like
+/// [`locate::locate_triangle`] does. For a vertex first appearing at binning depth `d`, this runs in
+/// O(d²).
+///
+/// Panics if no vertex has the ID. See [`try_canonical_vertex_position`].
+pub fn canonical_vertex_position(canonical_id: usize, midpoint_rule: MidpointRule) -> Vec3 {
+    try_canonical_vertex_position(canonical_id, midpoint_rule)
+        .unwrap_or_else(|error| panic!("{error}"))
+}
+
+/// Same as [`canonical_vertex_position`], but returns an error instead of panicking if no vertex has
+/// the ID. See [`try_canonical_vertex_depth`].
+pub fn try_canonical_vertex_position(
+    canonical_id: usize,
+    midpoint_rule: MidpointRule,
+) -> Result<Vec3, IcosphereError> {
+    let binning_depth = try_canonical_vertex_depth(canonical_id)?;
+
+    if binning_depth == 0 {
+        return Ok(icosahedron_positions()[canonical_id]);
+    }
+
+    // The vertex splits an edge of the previous binning depth
+    let binning_depth = binning_depth - 1;
+    let (triangle_index, edge) =
+        edge_triangle(canonical_id - vertex_count(binning_depth), binning_depth);
+
+    let corners = locate::triangle_corners(triangle_index, binning_depth, midpoint_rule);
+    Ok(locate::child_corners(corners, 3, midpoint_rule)[edge])
+}
+
+/// Finds the vertex closest to `direction` in an icosphere of the given binning depth, whose vertices
+/// were placed with the given rule, as its canonical ID and the angle between them in radians.
+///
+/// Like [`canonical_vertex_position`], this doesn't need an icosphere to exist. The closest vertex is
+/// searched for among the corners of the triangle containing `direction` (see
+/// [`locate::locate_triangle_with`]) and of every triangle touching it, which always includes the
+/// closest vertex in a mesh as close to a Delaunay triangulation as an icosphere.
+///
+/// Positions are `f32`, so past binning depth 20 or so, neighboring vertices are too close together
+/// for this to reliably return a vertex when given its own position.
+///
+/// `direction` doesn't need to be normalized, but it must not be zero.
+pub fn nearest_canonical_vertex(
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> (usize, f32) {
+    let direction = direction.normalize();
+    let triangle_index = locate::locate_triangle_with(direction, binning_depth, midpoint_rule);
+
+    let mut triangle_indices = adjacency::vertex_neighbors(triangle_index, binning_depth);
+    triangle_indices.extend(adjacency::edge_neighbors(triangle_index, binning_depth));
+    triangle_indices.push(triangle_index);
+
+    triangle_indices
+        .into_iter()
+        .flat_map(|triangle_index| {
+            let ids = canonical_triangle_vertices(triangle_index, binning_depth);
+            let corners = locate::triangle_corners(triangle_index, binning_depth, midpoint_rule);
+
+            ids.into_iter().zip(corners)
+        })
+        // Chord lengths stay precise at binning depths where the cosines of neighboring vertices
+        // round to the same `f32`
+        .map(|(canonical_id, position)| (canonical_id, position.normalize().distance(direction)))
+        .min_by(|(a, x), (b, y)| x.total_cmp(y).then(a.cmp(b)))
+        .map(|(canonical_id, chord)| (canonical_id, 2.0 * (chord / 2.0).min(1.0).asin()))
+        .unwrap()
+}
+
+/// The canonical ID of every vertex of an icosphere, and the other way around. See
+/// [`canonical_triangle_vertices`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CanonicalVertices {
+    /// For each vertex in [`Icosphere::vertices`], its canonical ID, or `None` if no generated
+    /// triangle uses it.
+    pub canonical_ids: Vec<Option<usize>>,
+
+    /// For each canonical ID of a vertex used by a generated triangle, its index in
+    /// [`Icosphere::vertices`].
+    pub vertex_indices: HashMap<usize, usize>,
+}
+
+impl CanonicalVertices {
+    /// Finds the canonical ID of every vertex used by a generated triangle.
+    pub fn new<T: IcosphereVertex, S: Icosphere<T> + ?Sized>(ico: &S) -> Self {
+        let mut canonical_ids = vec![None; ico.vertices().len()];
+        let mut vertex_indices = HashMap::new();
+
+        for triangle_index in ico.allocated_triangle_indices() {
+            let ids = canonical_triangle_vertices(triangle_index, ico.binning_depth());
+
+            for (vertex_index, canonical_id) in ico.triangle(triangle_index).into_iter().zip(ids) {
+                canonical_ids[vertex_index as usize] = Some(canonical_id);
+                vertex_indices.insert(canonical_id, vertex_index as usize);
+            }
+        }
+
+        Self {
+            canonical_ids,
+            vertex_indices,
+        }
+    }
+}
+
+impl<T: IcosphereVertex> StaticIcosphere<T> {
+    /// Reorders the vertices so that every vertex index is its canonical ID (see
+    /// [`canonical_triangle_vertices`]), updating every index that refers to them. Icospheres
+    /// generated in any way then list the same vertices in the same order.
+    pub fn canonicalize(&mut self) {
+        let canonical = CanonicalVertices::new(self);
+        let remap = |vertex_index: usize| canonical.canonical_ids[vertex_index].unwrap();
+
+        let mut vertices: Vec<Option<T>> = vec![None; self.vertices.len()];
+        for (vertex_index, vertex) in self.vertices.drain(..).enumerate() {
+            vertices[remap(vertex_index)] = Some(vertex);
+        }
+        self.vertices = vertices.into_iter().map(Option::unwrap).collect();
+
+        for triangle in &mut self.triangles {
+            *triangle = triangle.map(|vertex_index| remap(vertex_index as usize) as u32);
+        }
+
+        self.neighbors = self
+            .neighbors
+            .drain()
+            .map(|(vertex_index, neighbors)| {
+                let neighbors: HashSet<usize> = neighbors.into_iter().map(remap).collect();
+                (remap(vertex_index), neighbors)
+            })
+            .collect();
+
+        self.midpoints = self
+            .midpoints
+            .drain()
+            .map(|((i, j), midpoint)| {
+                let (i, j) = (remap(i), remap(j));
+                ((i.min(j), i.max(j)), remap(midpoint))
+            })
+            .collect();
+    }
+}
+
+/// Canonical IDs of the corners and edges of a triangle, found by descending from its face of the
+/// regular icosahedron, like [`locate::triangle_corners`].
+fn canonical_triangle(triangle_index: usize, binning_depth: usize) -> ([usize; 3], [usize; 3]) {
+    let face = locate::ancestor_index(triangle_index, binning_depth);
+    let mut corners = ICOSAHEDRON_TRIANGLES[face].map(|i| i as usize);
+    let mut edges = [0, 1, 2].map(|edge| base_edge_index(corners[edge], corners[(edge + 1) % 3]));
+
+    for depth in 0..binning_depth {
+        let parent_index = locate::ancestor_index(triangle_index, binning_depth - depth);
+        let child = locate::ancestor_index(triangle_index, binning_depth - depth - 1) % 4;
+
+        let [a, b, c] = corners;
+        let [d, e, f] = edges.map(|edge| vertex_count(depth) + edge);
+
+        // Edges at the next depth: first the center child's edges, then the halves of parent edges
+        let interior = |edge: usize| 3 * parent_index + edge;
+        let half = |edge: usize, corner: usize| {
+            let other = if corners[edge] == corner {
+                corners[(edge + 1) % 3]
+            } else {
+                corners[edge]
+            };
+
+            3 * triangle_count(depth) + 2 * edges[edge] + usize::from(corner > other)
+        };
+
+        // Edge `k` of the parent is split into edge 0 of child `k` and edge 2 of child `k + 1`, and
+        // edge 1 of every corner child is an edge of the center child
+        (corners, edges) = match child {
+            3 => ([d, e, f], [interior(0), interior(1), interior(2)]),
+            child => {
+                let corner = [a, b, c][child];
+                let child_corners = match child {
+                    0 => [a, d, f],
+                    1 => [b, e, d],
+                    _ => [c, f, e],
+                };
+
+                (
+                    child_corners,
+                    [
+                        half(child, corner),
+                        interior((child + 2) % 3),
+                        half((child + 2) % 3, corner),
+                    ],
+                )
+            }
+        };
+    }
+
+    (corners, edges)
+}
+
+/// A triangle with the edge of the given canonical index at the given binning depth, and which of
+/// its edges it is. The inverse of the edge numbering in [`canonical_triangle`].
+fn edge_triangle(edge_index: usize, binning_depth: usize) -> (usize, usize) {
+    if binning_depth == 0 {
+        let (a, b) = base_edges()[edge_index];
+
+        return ICOSAHEDRON_TRIANGLES
+            .iter()
+            .enumerate()
+            .find_map(|(triangle_index, corners)| {
+                let edge = (0..3).find(|&edge| {
+                    corners[edge] as usize == a && corners[(edge + 1) % 3] as usize == b
+                })?;
+
+                Some((triangle_index, edge))
+            })
+            .unwrap();
+    }
+
+    let interior_count = 3 * triangle_count(binning_depth - 1);
+
+    // An edge of the center child of a triangle at the previous depth
+    if edge_index < interior_count {
+        return (edge_index / 3 * 4 + 3, edge_index % 3);
+    }
+
+    // Half of an edge at the previous depth, touching its corner with the lower or higher ID
+    let half = edge_index - interior_count;
+    let (parent_index, edge) = edge_triangle(half / 2, binning_depth - 1);
+    let (corners, _) = canonical_triangle(parent_index, binning_depth - 1);
+
+    let (from, to) = (corners[edge], corners[(edge + 1) % 3]);
+    let touches_from = (half % 2 == 1) == (from > to);
+
+    // Edge `k` of the parent is split into edge 0 of child `k` and edge 2 of child `k + 1`
+    if touches_from {
+        (parent_index * 4 + edge, 0)
+    } else {
+        (parent_index * 4 + (edge + 1) % 3, 2)
+    }
+}
+
+/// Index of an edge of the regular icosahedron, among all of its edges sorted by their vertex
+/// indices.
+fn base_edge_index(a: usize, b: usize) -> usize {
+    base_edges().binary_search(&(a.min(b), a.max(b))).unwrap()
+}
+
+/// Every edge of the regular icosahedron as sorted vertex indices, in order.
+fn base_edges() -> Vec<(usize, usize)> {
+    let mut edges: Vec<(usize, usize)> = ICOSAHEDRON_TRIANGLES
+        .iter()
+        .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+        .map(|(a, b)| (a.min(b) as usize, a.max(b) as usize))
+        .collect();
+
+    edges.sort_unstable();
+    edges.dedup();
+    edges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SparseIcosphere;
+
+    #[test]
+    fn ids_are_exactly_the_vertex_count() {
+        for binning_depth in 0..4 {
+            let canonical = StaticIcosphere::<Vec3>::nth(binning_depth).canonical_vertices();
+
+            let mut ids: Vec<usize> = canonical.canonical_ids.into_iter().flatten().collect();
+            ids.sort_unstable();
+
+            assert_eq!(ids, (0..vertex_count(binning_depth)).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn vertices_keep_their_ids_when_subdividing() {
+        for binning_depth in 0..4 {
+            for triangle_index in 0..triangle_count(binning_depth) {
+                let parent = canonical_triangle_vertices(triangle_index, binning_depth);
+
+                // Corner `k` of the parent is corner 0 of child `k`
+                for (k, &id) in parent.iter().enumerate() {
+                    let child =
+                        canonical_triangle_vertices(triangle_index * 4 + k, binning_depth + 1);
+                    assert_eq!(child[0], id);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn vertex_depth_is_bounded() {
+        assert_eq!(canonical_vertex_depth(11), 0);
+        assert_eq!(canonical_vertex_depth(12), 1);
+        assert_eq!(canonical_vertex_depth(vertex_count(3) - 1), 3);
+        assert_eq!(
+            canonical_vertex_depth(vertex_count(MAX_BINNING_DEPTH) - 1),
+            MAX_BINNING_DEPTH
+        );
+
+        let error = IcosphereError::CanonicalIdOutOfRange {
+            canonical_id: usize::MAX,
+            vertex_count: vertex_count(MAX_BINNING_DEPTH),
+        };
+        assert_eq!(try_canonical_vertex_depth(usize::MAX), Err(error));
+        assert_eq!(
+            try_canonical_vertex_position(usize::MAX, MidpointRule::Normalized),
+            Err(error)
+        );
+    }
+
+    #[test]
+    fn vertex_positions_match_static_icosphere() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(3);
+        ico.canonicalize();
+
+        for (canonical_id, &position) in ico.vertices.iter().enumerate() {
+            let found = canonical_vertex_position(canonical_id, MidpointRule::Normalized);
+            assert!(found.distance(position) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn ids_match_regardless_of_generation_order() {
+        let previous = SparseIcosphere::<Vec3>::filled(1);
+        let mut sparse = SparseIcosphere::empty(2);
+        for parent_index in (0..triangle_count(1)).rev() {
+            sparse.subdivide_chunk(&previous, parent_index);
+        }
+
+        let mut canonicalized = StaticIcosphere::<Vec3>::nth(2);
+        canonicalized.canonicalize();
+
+        let canonical = sparse.canonical_vertices();
+        assert_eq!(canonical.vertex_indices.len(), vertex_count(2));
+
+        for (vertex_index, canonical_id) in canonical.canonical_ids.iter().enumerate() {
+            let canonical_id = canonical_id.unwrap();
+
+            assert_eq!(canonical.vertex_indices[&canonical_id], vertex_index);
+            assert_eq!(
+                sparse.vertices[vertex_index],
+                canonicalized.vertices[canonical_id]
+            );
+        }
+    }
+}
@@ -0,0 +1,414 @@
+use glam::{Mat4, Vec3, Vec4};
+
+use crate::{Icosphere, IcosphereVertex, levels::IcosphereLevels};
+
+/// How much larger than the covering triangle the bounds of a chunk are made. With
+/// [`crate::midpoint::MidpointRule::EqualArea`], edges inside a triangle bulge slightly past the
+/// great circles between its corners.
+pub(crate) const BOUNDS_MARGIN: f32 = 1.05;
+
+/// Bounding volumes of a chunk of [`IcosphereLevels`], on the unit sphere. Use [`Self::scaled`] for
+/// spheres of other radii.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ChunkBounds {
+    /// Center of a sphere containing every triangle of the chunk.
+    pub center: Vec3,
+    pub radius: f32,
+
+    /// Axis of a cone containing the normals of every triangle of the chunk.
+    pub cone_axis: Vec3,
+
+    /// Angle between the cone's axis and its surface, in radians.
+    pub cone_angle: f32,
+
+    /// The largest angle between [`Self::cone_axis`] and a vertex of the chunk, in radians.
+    pub cap_angle: f32,
+
+    /// The largest distance between the chunk's triangles and the sphere.
+    pub error: f32,
+}
+
+impl ChunkBounds {
+    /// Bounds of the triangles made by subdividing the triangle with the given corners
+    /// `subdivisions` times.
+    pub(crate) fn from_cover(corners: [Vec3; 3], subdivisions: usize) -> Self {
+        let axis = (corners[0] + corners[1] + corners[2]).normalize();
+
+        // Every vertex of the chunk is on the cap around the axis that reaches the farthest corner
+        let cap_angle = corners
+            .iter()
+            .map(|corner| axis.angle_between(*corner))
+            .fold(0.0, f32::max)
+            * BOUNDS_MARGIN;
+
+        // Subdividing roughly halves the size of the triangles each time. The center child is the
+        // largest, and its corners are about halfway to the parent's corners on the tangent plane.
+        let triangle_angle =
+            (0..subdivisions).fold(cap_angle, |angle, _| (angle.tan() / 2.0).atan());
+
+        // A cap up to a quarter of the sphere is contained by the sphere through its rim, and larger
+        // caps by the whole unit sphere
+        let (center, radius) = if cap_angle < std::f32::consts::FRAC_PI_2 {
+            (axis * cap_angle.cos(), cap_angle.sin())
+        } else {
+            (Vec3::ZERO, 1.0)
+        };
+
+        Self {
+            center,
+            radius,
+            cone_axis: axis,
+            // A triangle's normal points to the center of its circumcircle on the sphere
+            cone_angle: cap_angle + triangle_angle,
+            cap_angle,
+            // The center of a flat triangle is the farthest point from the sphere
+            error: 1.0 - triangle_angle.cos(),
+        }
+    }
+
+    /// The same bounds for a sphere of the given radius.
+    pub fn scaled(&self, radius: f32) -> Self {
+        Self {
+            center: self.center * radius,
+            radius: self.radius * radius,
+            error: self.error * radius,
+            ..*self
+        }
+    }
+
+    /// The same bounds for a sphere of the given radius, with every vertex moved up or down along
+    /// the normal of the sphere by a height in `min_height..=max_height`. See
+    /// [`crate::displacement`].
+    ///
+    /// The normals of displaced triangles can point anywhere, so the cone no longer bounds them and
+    /// [`Self::is_behind_horizon`] always returns `false`. Use [`Self::is_occluded_by_sphere`]
+    /// instead. The error only accounts for the curvature of the sphere, not for the detail of the
+    /// heights.
+    pub fn displaced(&self, radius: f32, min_height: f32, max_height: f32) -> Self {
+        let (inner, outer) = (radius + min_height, radius + max_height);
+
+        // Every vertex is between the inner and the outer sphere, on the cap. The farthest points
+        // from any center on the axis are on the rims of the cap.
+        let (center, bounding_radius) = if self.cap_angle < std::f32::consts::FRAC_PI_2 {
+            let cos_cap = self.cap_angle.cos();
+            let height = (inner * cos_cap + outer) / 2.0;
+
+            let rim_distance = |rim_radius: f32| {
+                (rim_radius * rim_radius - 2.0 * rim_radius * height * cos_cap + height * height)
+                    .sqrt()
+            };
+
+            (
+                self.cone_axis * height,
+                rim_distance(inner).max(rim_distance(outer)),
+            )
+        } else {
+            (Vec3::ZERO, outer)
+        };
+
+        Self {
+            center,
+            radius: bounding_radius,
+            cone_angle: std::f32::consts::PI,
+            error: self.error * outer,
+            ..*self
+        }
+    }
+
+    /// Whether the bounding sphere is completely hidden from `eye` by a sphere of radius
+    /// `occluder_radius` at the origin. Unlike [`Self::is_behind_horizon`], this works for
+    /// [`Self::displaced`] bounds, as long as the occluder is inside the rendered surface.
+    pub fn is_occluded_by_sphere(&self, eye: Vec3, occluder_radius: f32) -> bool {
+        let eye_distance = eye.length();
+
+        if eye_distance <= occluder_radius {
+            return false;
+        }
+
+        let to_center = self.center - eye;
+        let center_distance = to_center.length();
+
+        if center_distance <= self.radius {
+            return false;
+        }
+
+        // Every ray from the eye towards the bounding sphere must hit the occluder, and it must hit
+        // it before reaching any point of the bounding sphere. Rays touching the occluder do so at
+        // the tangent distance, and every other ray hits it sooner.
+        let occluder_angle = (occluder_radius / eye_distance).asin();
+        let bounds_angle = (self.radius / center_distance).asin();
+        let tangent_distance =
+            (eye_distance * eye_distance - occluder_radius * occluder_radius).sqrt();
+
+        to_center.angle_between(-eye) + bounds_angle <= occluder_angle
+            && center_distance - self.radius >= tangent_distance
+    }
+
+    /// Whether any part of the bounding sphere is inside the frustum.
+    pub fn intersects_frustum(&self, frustum: &Frustum) -> bool {
+        frustum.intersects_sphere(self.center, self.radius)
+    }
+
+    /// Whether every triangle of the chunk faces away from `eye`. The icosphere is convex, so from
+    /// outside, these are exactly the triangles behind its horizon.
+    pub fn is_behind_horizon(&self, eye: Vec3) -> bool {
+        if self.cone_angle >= std::f32::consts::FRAC_PI_2 {
+            return false;
+        }
+
+        // A triangle faces away if the direction from the eye to it is within 90° of its normal,
+        // minus how far the normal can be from the axis. This must hold anywhere in the bounding
+        // sphere.
+        let to_center = self.center - eye;
+
+        to_center.dot(self.cone_axis)
+            >= self.cone_angle.sin() * to_center.length()
+                + self.radius * (1.0 + self.cone_angle.sin())
+    }
+}
+
+/// A view frustum as six planes, with normals pointing inside.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Frustum {
+    /// Planes in the order left, right, bottom, top, near, far. A point `p` is on the inner side of a
+    /// plane if `plane.truncate().dot(p) + plane.w >= 0`. The normals have unit length.
+    pub planes: [Vec4; 6],
+}
+
+impl Frustum {
+    /// Extracts the frustum from a combined projection and view matrix, which maps depth to `0..=1`,
+    /// like [`Mat4::perspective_rh`] does.
+    pub fn from_view_projection(view_projection: Mat4) -> Self {
+        let [x, y, z, w] = [0, 1, 2, 3].map(|i| view_projection.row(i));
+
+        let planes =
+            [w + x, w - x, w + y, w - y, z, w - z].map(|plane| plane / plane.truncate().length());
+
+        Self { planes }
+    }
+
+    /// Whether any part of the sphere is inside the frustum. Spheres near the frustum's corners may
+    /// be reported as intersecting even if they aren't.
+    pub fn intersects_sphere(&self, center: Vec3, radius: f32) -> bool {
+        self.planes
+            .iter()
+            .all(|plane| plane.truncate().dot(center) + plane.w >= -radius)
+    }
+}
+
+impl<T, S> IcosphereLevels<T, S>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    /// Bounding volumes of a chunk, on the unit sphere. The chunk itself doesn't need to be generated,
+    /// only the one containing it at the level below, so chunks can be culled before generating them.
+    pub fn chunk_bounds(&self, level: usize, chunk_index: usize) -> ChunkBounds {
+        let subdivisions = if level == 0 {
+            0
+        } else {
+            self.binning_depth_step
+        };
+
+        ChunkBounds::from_cover(self.chunk_cover(level, chunk_index), subdivisions)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{StaticIcosphere, locate};
+
+    /// Binning depth of the triangles covering each chunk, and of the chunks' triangles.
+    const COVER_DEPTH: usize = 2;
+    const CHUNK_DEPTH: usize = 4;
+
+    /// Corners of a triangle at the cover depth.
+    fn cover_corners(cover_index: usize) -> [Vec3; 3] {
+        let ico = StaticIcosphere::<Vec3>::nth(COVER_DEPTH);
+        ico.triangles[cover_index].map(|i| ico.vertices[i as usize])
+    }
+
+    /// Bounds of every chunk, with the corners of its triangles.
+    fn chunks() -> Vec<(ChunkBounds, Vec<[Vec3; 3]>)> {
+        let ico = StaticIcosphere::<Vec3>::nth(CHUNK_DEPTH);
+        let chunk_size = 1 << (2 * (CHUNK_DEPTH - COVER_DEPTH));
+
+        (0..crate::triangle_count(COVER_DEPTH))
+            .map(|cover_index| {
+                let bounds =
+                    ChunkBounds::from_cover(cover_corners(cover_index), CHUNK_DEPTH - COVER_DEPTH);
+
+                let triangles = (cover_index * chunk_size..(cover_index + 1) * chunk_size)
+                    .map(|triangle_index| {
+                        ico.triangles[triangle_index].map(|i| ico.vertices[i as usize])
+                    })
+                    .collect();
+
+                (bounds, triangles)
+            })
+            .collect()
+    }
+
+    const EYES: [Vec3; 3] = [
+        Vec3::new(0.0, 0.0, 3.0),
+        Vec3::new(1.1, 0.4, -0.2),
+        Vec3::new(-20.0, 5.0, 9.0),
+    ];
+
+    #[test]
+    fn bounds_contain_the_chunk() {
+        for (bounds, triangles) in chunks() {
+            for [a, b, c] in triangles {
+                for point in [a, b, c, (a + b + c) / 3.0] {
+                    assert!(point.distance(bounds.center) <= bounds.radius);
+                    assert!(1.0 - point.length() <= bounds.error + 1e-6);
+                }
+
+                let normal = (b - a).cross(c - a).normalize();
+                assert!(normal.angle_between(bounds.cone_axis) <= bounds.cone_angle);
+            }
+        }
+    }
+
+    #[test]
+    fn only_chunks_facing_away_are_behind_the_horizon() {
+        let chunks = chunks();
+
+        for eye in EYES {
+            let mut behind_count = 0;
+
+            for (bounds, triangles) in &chunks {
+                let faces_eye = triangles
+                    .iter()
+                    .any(|&[a, b, c]| (b - a).cross(c - a).dot(a - eye) < 0.0);
+
+                if bounds.is_behind_horizon(eye) {
+                    assert!(!faces_eye);
+                    behind_count += 1;
+                }
+            }
+
+            // The half of the sphere facing away, minus the chunks along the horizon
+            assert!(behind_count > chunks.len() / 4, "{behind_count}");
+        }
+    }
+
+    #[test]
+    fn chunk_across_the_horizon_is_visible() {
+        let eye = Vec3::Z * 3.0;
+
+        // Triangles are seen from the eye up to this angle from it
+        let horizon = (1.0 / 3.0f32).acos();
+        let direction = Vec3::new(horizon.sin(), 0.0, horizon.cos());
+
+        let cover_index = locate::locate_triangle(direction, COVER_DEPTH);
+        let bounds = ChunkBounds::from_cover(cover_corners(cover_index), CHUNK_DEPTH - COVER_DEPTH);
+
+        assert!(!bounds.is_behind_horizon(eye));
+        assert!(!bounds.is_occluded_by_sphere(eye, 1.0));
+    }
+
+    #[test]
+    fn only_hidden_chunks_are_occluded() {
+        let (min_height, max_height) = (-0.05, 0.1);
+        let chunks = chunks();
+
+        for eye in EYES {
+            let mut occluded_count = 0;
+
+            for (bounds, triangles) in &chunks {
+                let displaced = bounds.displaced(1.0, min_height, max_height);
+
+                if !displaced.is_occluded_by_sphere(eye, 1.0 + min_height) {
+                    continue;
+                }
+
+                occluded_count += 1;
+
+                // The segment from the eye to every displaced vertex crosses the occluder
+                for &vertex in triangles.iter().flatten() {
+                    for point in [vertex * (1.0 + min_height), vertex * (1.0 + max_height)] {
+                        let to_point = point - eye;
+                        let closest =
+                            (-eye.dot(to_point) / to_point.length_squared()).clamp(0.0, 1.0);
+
+                        assert!((eye + to_point * closest).length() < 1.0 + min_height);
+                    }
+                }
+            }
+
+            assert!(occluded_count > chunks.len() / 8, "{occluded_count}");
+        }
+
+        // The eye must be outside the occluder to be occluded by it
+        let (bounds, _) = &chunks[0];
+        assert!(!bounds.is_occluded_by_sphere(-bounds.cone_axis * 0.5, 1.0));
+    }
+
+    #[test]
+    fn displaced_bounds_contain_the_displaced_chunk() {
+        let (min_height, max_height) = (-0.05, 0.1);
+
+        for (bounds, triangles) in chunks() {
+            let displaced = bounds.displaced(2.0, min_height, max_height);
+
+            for &vertex in triangles.iter().flatten() {
+                for height in [min_height, max_height] {
+                    let point = vertex * (2.0 + height);
+                    assert!(point.distance(displaced.center) <= displaced.radius + 1e-5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn frustum_planes() {
+        let eye = Vec3::Z * 5.0;
+        let view = Mat4::look_at_rh(eye, Vec3::ZERO, Vec3::Y);
+        let projection = Mat4::perspective_rh(std::f32::consts::FRAC_PI_2, 1.0, 1.0, 10.0);
+        let frustum = Frustum::from_view_projection(projection * view);
+
+        let distance = |plane: usize, point: Vec3| {
+            let plane = frustum.planes[plane];
+            plane.truncate().dot(point) + plane.w
+        };
+
+        for plane in frustum.planes {
+            assert!((plane.truncate().length() - 1.0).abs() < 1e-5);
+        }
+
+        // The center is inside every plane, by the distance to the near and far planes, and to the
+        // sides of the 90° field of view
+        let side_distance = 5.0 * std::f32::consts::FRAC_1_SQRT_2;
+        let expected = [
+            side_distance,
+            side_distance,
+            side_distance,
+            side_distance,
+            4.0,
+            5.0,
+        ];
+
+        for (plane, expected) in expected.into_iter().enumerate() {
+            assert!((distance(plane, Vec3::ZERO) - expected).abs() < 1e-3);
+        }
+
+        // The left plane faces right
+        assert!(distance(0, Vec3::X) > distance(0, Vec3::ZERO));
+        assert!(distance(1, Vec3::X) < distance(1, Vec3::ZERO));
+
+        assert!(frustum.intersects_sphere(Vec3::ZERO, 0.1));
+
+        // Behind the eye, past the far plane, and to the side, by more than the radius or not
+        for (center, outside_radius, inside_radius) in [
+            (Vec3::Z * 6.0, 0.5, 2.5),
+            (Vec3::Z * -6.0, 0.5, 1.5),
+            (Vec3::X * 6.0, 0.5, 1.0),
+            (Vec3::Y * -6.0, 0.5, 1.0),
+        ] {
+            assert!(!frustum.intersects_sphere(center, outside_radius));
+            assert!(frustum.intersects_sphere(center, inside_radius));
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::{fmt, marker::PhantomData};
+
+use glam::Vec3;
+
+use crate::IcosphereVertex;
+
+/// Angle between the two ends of an edge of the regular icosahedron, in radians.
+const BASE_EDGE_ANGLE: f32 = 1.107_148_7;
+
+/// Heights of a terrain on top of a sphere, for [`DisplacedVertex`].
+///
+/// Heights only depend on the direction and the binning depth a vertex is created at, so vertices
+/// shared between chunks get the same height, and since subdividing copies existing vertices, a
+/// vertex keeps the height of the binning depth it first appeared at in every level of
+/// [`crate::levels::IcosphereLevels`].
+///
+/// Heightmaps are types rather than values, with no `self`, because vertices are created through
+/// [`IcosphereVertex::from_position`], which has no way to pass one along. That means a heightmap
+/// can't hold state chosen at runtime, like a seed or loaded elevation data, unless it reads it from
+/// a `static` (for example a `OnceLock` set before generating). Otherwise, generate the icosphere
+/// with plain [`Vec3`] vertices and displace the positions afterwards.
+pub trait Heightmap {
+    /// Radius of the sphere that heights are measured from.
+    const RADIUS: f32;
+
+    /// Height above the sphere in the given unit direction. The binning depth can be used to add
+    /// detail to finer subdivisions.
+    fn height(direction: Vec3, binning_depth: usize) -> f32;
+}
+
+/// A vertex displaced along the normal of the sphere by a [`Heightmap`].
+///
+/// [`IcosphereVertex::position`] stays on the unit sphere, so that subdivisions, lookups and
+/// raycasts keep working on the undisplaced sphere. The displaced position and normal are
+/// [`IcosphereVertex::surface_position`] and [`IcosphereVertex::surface_normal`], which are what the
+/// exporters and [`crate::attributes::RenderMesh`] use.
+pub struct DisplacedVertex<H: Heightmap> {
+    /// Position on the unit sphere.
+    pub direction: Vec3,
+    pub height: f32,
+
+    /// `direction * (H::RADIUS + height)`.
+    pub displaced_position: Vec3,
+
+    /// Normal of the displaced surface, from heights sampled half an edge length away in every
+    /// direction, so it is as smooth as the mesh at the binning depth the vertex was created at.
+    pub normal: Vec3,
+
+    _phantom: PhantomData<fn() -> H>,
+}
+
+impl<H: Heightmap> DisplacedVertex<H> {
+    /// Displaces the point on the sphere in the given direction.
+    pub fn new(direction: Vec3, binning_depth: usize) -> Self {
+        let direction = direction.normalize();
+        let height = H::height(direction, binning_depth);
+
+        let offset = half_edge_angle(binning_depth).tan();
+        let (tangent, bitangent) = direction.any_orthonormal_pair();
+
+        let sample = |step: Vec3| {
+            let sample_direction = (direction + step * offset).normalize();
+            sample_direction * (H::RADIUS + H::height(sample_direction, binning_depth))
+        };
+
+        let normal = (sample(tangent) - sample(-tangent))
+            .cross(sample(bitangent) - sample(-bitangent))
+            .normalize_or(direction);
+
+        // The tangents may be in either order
+        let normal = if normal.dot(direction) < 0.0 {
+            -normal
+        } else {
+            normal
+        };
+
+        Self {
+            direction,
+            height,
+            displaced_position: direction * (H::RADIUS + height),
+            normal,
+            _phantom: PhantomData,
+        }
+    }
+}
+
+/// Approximate angle between the ends of half an edge at the given binning depth, since every
+/// subdivision halves the edges.
+fn half_edge_angle(binning_depth: usize) -> f32 {
+    BASE_EDGE_ANGLE / (1u64 << (binning_depth + 1)) as f32
+}
+
+impl<H: Heightmap> IcosphereVertex for DisplacedVertex<H> {
+    fn position(&self) -> Vec3 {
+        self.direction
+    }
+
+    fn from_position(position: Vec3, binning_depth: usize) -> Self {
+        Self::new(position, binning_depth)
+    }
+
+    fn surface_position(&self) -> Vec3 {
+        self.displaced_position
+    }
+
+    fn surface_normal(&self) -> Vec3 {
+        self.normal
+    }
+}
+
+// Derives would require the heightmap to implement these too
+
+impl<H: Heightmap> Clone for DisplacedVertex<H> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<H: Heightmap> Copy for DisplacedVertex<H> {}
+
+impl<H: Heightmap> PartialEq for DisplacedVertex<H> {
+    fn eq(&self, other: &Self) -> bool {
+        self.direction == other.direction
+            && self.height == other.height
+            && self.displaced_position == other.displaced_position
+            && self.normal == other.normal
+    }
+}
+
+impl<H: Heightmap> fmt::Debug for DisplacedVertex<H> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DisplacedVertex")
+            .field("direction", &self.direction)
+            .field("height", &self.height)
+            .field("displaced_position", &self.displaced_position)
+            .field("normal", &self.normal)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Icosphere, StaticIcosphere};
+
+    struct Flat;
+
+    impl Heightmap for Flat {
+        const RADIUS: f32 = 10.0;
+
+        fn height(_direction: Vec3, _binning_depth: usize) -> f32 {
+            0.5
+        }
+    }
+
+    #[test]
+    fn flat_heightmap_is_a_sphere() {
+        let ico = StaticIcosphere::<DisplacedVertex<Flat>>::nth(2);
+
+        for vertex in &ico.vertices {
+            assert!((vertex.surface_position().length() - 10.5).abs() < 1e-4);
+            assert!(vertex.surface_normal().dot(vertex.direction) > 0.9999);
+        }
+    }
+
+    #[test]
+    fn parent_positions_are_between_displaced_vertices() {
+        let previous = StaticIcosphere::<DisplacedVertex<Flat>>::nth(1);
+        let ico = previous.subdivide();
+
+        for (vertex_index, parent_position) in ico.parent_positions().into_iter().enumerate() {
+            let vertex = ico.vertices[vertex_index];
+
+            if vertex_index < previous.vertices.len() {
+                assert_eq!(parent_position, vertex.displaced_position);
+                continue;
+            }
+
+            // The ends of the edge the vertex split
+            let (a, b) = previous
+                .neighbors
+                .iter()
+                .flat_map(|(&a, neighbors)| neighbors.iter().map(move |&b| (a, b)))
+                .map(|(a, b)| (previous.vertices[a], previous.vertices[b]))
+                .find(|(a, b)| {
+                    (a.direction + b.direction)
+                        .normalize()
+                        .distance(vertex.direction)
+                        < 1e-6
+                })
+                .unwrap();
+
+            let expected = (a.displaced_position + b.displaced_position) / 2.0;
+            assert!(parent_position.distance(expected) < 1e-4);
+        }
+    }
+
+    #[test]
+    fn half_edge_angle_matches_mesh() {
+        for binning_depth in 0..5 {
+            let ico = StaticIcosphere::<Vec3>::nth(binning_depth);
+            let [a, b, _] = ico.triangles[0].map(|i| ico.vertices[i as usize]);
+
+            let ratio = a.angle_between(b) / (2.0 * half_edge_angle(binning_depth));
+            assert!((0.8..1.2).contains(&ratio));
+        }
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use glam::Vec3;
+
+use crate::{Icosphere, IcosphereVertex};
+
+/// The dual of an icosphere, also known as a Goldberg polyhedron or hexasphere: one polygonal cell per
+/// icosphere vertex, with a corner at the center of every icosphere triangle around it. The twelve
+/// vertices of the regular icosahedron become pentagons, and every other vertex becomes a hexagon.
+#[derive(Debug, Clone)]
+pub struct DualMesh {
+    /// For each cell, the index of the icosphere vertex it is centered on.
+    pub cell_vertices: Vec<usize>,
+
+    /// For each cell, the position of the icosphere vertex it is centered on.
+    pub centers: Vec<Vec3>,
+
+    /// For each cell, its corners as indices into [`Self::corners`], ordered counter-clockwise when
+    /// viewed from outside.
+    pub cell_corners: Vec<Vec<usize>>,
+
+    /// For each cell, the cells across each of its edges. The cell across the edge from
+    /// `cell_corners[cell][i]` to `cell_corners[cell][i + 1]` is `adjacency[cell][i]`, or `None` if
+    /// that cell wasn't built (see [`Self::new`]), so this always has as many entries as the corners.
+    pub adjacency: Vec<Vec<Option<usize>>>,
+
+    /// Corner positions, which are the centroids of icosphere triangles projected onto the unit sphere.
+    pub corners: Vec<Vec3>,
+
+    /// For each corner, the index of the icosphere triangle it is the center of.
+    pub corner_triangles: Vec<usize>,
+}
+
+/// A triangulation of a [`DualMesh`] for rendering, where each cell is a fan around its center.
+/// Vertices aren't shared between cells, so every vertex belongs to exactly one cell.
+#[derive(Debug, Clone)]
+pub struct DualRenderMesh {
+    pub positions: Vec<Vec3>,
+
+    /// For each vertex, the index of the cell it belongs to.
+    pub cell_ids: Vec<u32>,
+
+    /// Triangles that index into [`Self::positions`], wound counter-clockwise when viewed from outside.
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl DualMesh {
+    /// Builds a cell for every vertex whose surrounding triangles are all generated. For a static
+    /// icosphere this is every vertex, and for a sparse icosphere it's the interior of the generated
+    /// region. Cells are ordered by vertex index.
+    pub fn new<T: IcosphereVertex, S: Icosphere<T>>(ico: &S) -> Self {
+        let vertices = ico.vertices();
+        let neighbors = ico.neighbors();
+
+        // For each vertex, the triangles around it, keyed by the vertex that comes after it in the
+        // triangle's winding order, with the vertex that comes after that
+        let mut fans: HashMap<usize, HashMap<usize, (usize, usize)>> = HashMap::new();
+
+        let mut corners = Vec::new();
+        let mut corner_triangles = Vec::new();
+
+        for triangle_index in ico.allocated_triangle_indices() {
+            let [a, b, c] = ico.triangle(triangle_index).map(|i| i as usize);
+            let corner_index = corners.len();
+
+            corners.push(
+                (vertices[a].position() + vertices[b].position() + vertices[c].position())
+                    .normalize(),
+            );
+            corner_triangles.push(triangle_index);
+
+            for (vertex, next, after) in [(a, b, c), (b, c, a), (c, a, b)] {
+                fans.entry(vertex)
+                    .or_default()
+                    .insert(next, (after, corner_index));
+            }
+        }
+
+        let mut cell_vertices: Vec<usize> = fans
+            .iter()
+            .filter(|(vertex, fan)| {
+                neighbors
+                    .get(vertex)
+                    .is_some_and(|neighbors| neighbors.len() == fan.len())
+                    && is_closed(fan)
+            })
+            .map(|(&vertex, _)| vertex)
+            .collect();
+        cell_vertices.sort_unstable();
+
+        let cells: HashMap<usize, usize> = cell_vertices
+            .iter()
+            .enumerate()
+            .map(|(cell, &vertex)| (vertex, cell))
+            .collect();
+
+        let mut centers = Vec::with_capacity(cell_vertices.len());
+        let mut cell_corners = Vec::with_capacity(cell_vertices.len());
+        let mut adjacency = Vec::with_capacity(cell_vertices.len());
+
+        for &vertex in &cell_vertices {
+            let fan = &fans[&vertex];
+
+            let mut ring = Vec::with_capacity(fan.len());
+            let mut ring_corners = Vec::with_capacity(fan.len());
+
+            // Walking from each neighbor to the one after it goes counter-clockwise around the vertex
+            let start = *fan.keys().min().unwrap();
+            let mut next = start;
+
+            loop {
+                let (after, corner_index) = fan[&next];
+
+                ring.push(next);
+                ring_corners.push(corner_index);
+
+                next = after;
+                if next == start {
+                    break;
+                }
+            }
+
+            // The corner before a neighbor and the corner after it share the edge to that neighbor
+            ring.rotate_left(1);
+
+            centers.push(vertices[vertex].position());
+            cell_corners.push(ring_corners);
+            adjacency.push(
+                ring.into_iter()
+                    .map(|neighbor| cells.get(&neighbor).copied())
+                    .collect(),
+            );
+        }
+
+        Self {
+            cell_vertices,
+            centers,
+            cell_corners,
+            adjacency,
+            corners,
+            corner_triangles,
+        }
+    }
+
+    /// The number of cells.
+    pub fn cell_count(&self) -> usize {
+        self.cell_vertices.len()
+    }
+
+    /// Positions of the corners of a cell, in order.
+    pub fn cell_polygon(&self, cell: usize) -> Vec<Vec3> {
+        self.cell_corners[cell]
+            .iter()
+            .map(|&corner| self.corners[corner])
+            .collect()
+    }
+
+    /// Triangulates every cell for rendering.
+    pub fn render_mesh(&self) -> DualRenderMesh {
+        let mut positions = Vec::new();
+        let mut cell_ids = Vec::new();
+        let mut indices = Vec::new();
+
+        for (cell, cell_corners) in self.cell_corners.iter().enumerate() {
+            let center = positions.len() as u32;
+
+            positions.push(self.centers[cell]);
+            positions.extend(cell_corners.iter().map(|&corner| self.corners[corner]));
+            cell_ids.resize(positions.len(), cell as u32);
+
+            let corner_count = cell_corners.len() as u32;
+            for i in 0..corner_count {
+                indices.push([center, center + 1 + i, center + 1 + (i + 1) % corner_count]);
+            }
+        }
+
+        DualRenderMesh {
+            positions,
+            cell_ids,
+            indices,
+        }
+    }
+}
+
+/// Whether the triangles around a vertex close into a full ring.
+fn is_closed(fan: &HashMap<usize, (usize, usize)>) -> bool {
+    let Some(&start) = fan.keys().next() else {
+        return false;
+    };
+
+    let mut next = start;
+
+    for _ in 0..fan.len() {
+        match fan.get(&next) {
+            Some(&(after, _)) => next = after,
+            None => return false,
+        }
+    }
+
+    next == start
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere};
+
+    #[test]
+    fn adjacency_is_symmetric_and_aligned_with_corners() {
+        let dual = DualMesh::new(&StaticIcosphere::<Vec3>::nth(2));
+
+        assert_eq!(dual.cell_count(), 162);
+        assert_eq!(
+            dual.cell_corners.iter().filter(|c| c.len() == 5).count(),
+            12
+        );
+
+        for (cell, neighbors) in dual.adjacency.iter().enumerate() {
+            assert_eq!(neighbors.len(), dual.cell_corners[cell].len());
+
+            for (i, neighbor) in neighbors.iter().enumerate() {
+                let neighbor = neighbor.unwrap();
+                let j = dual.adjacency[neighbor]
+                    .iter()
+                    .position(|&other| other == Some(cell))
+                    .unwrap();
+
+                // Both cells share the corners of the edge between them, in opposite directions
+                let corners = &dual.cell_corners[cell];
+                let neighbor_corners = &dual.cell_corners[neighbor];
+
+                assert_eq!(
+                    corners[i],
+                    neighbor_corners[(j + 1) % neighbor_corners.len()]
+                );
+                assert_eq!(corners[(i + 1) % corners.len()], neighbor_corners[j]);
+            }
+        }
+    }
+
+    #[test]
+    fn cells_missing_from_sparse_icosphere_are_none() {
+        let regular = SparseIcosphere::<Vec3>::regular();
+        let mut ico = SparseIcosphere::empty(1);
+        for parent_index in 0..5 {
+            ico.subdivide_chunk(&regular, parent_index);
+        }
+
+        let dual = DualMesh::new(&ico);
+        assert!(dual.cell_count() > 0);
+
+        let mut missing = 0;
+        for (cell, neighbors) in dual.adjacency.iter().enumerate() {
+            assert_eq!(neighbors.len(), dual.cell_corners[cell].len());
+            missing += neighbors
+                .iter()
+                .filter(|neighbor| neighbor.is_none())
+                .count();
+        }
+
+        assert!(missing > 0);
+    }
+}
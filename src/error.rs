@@ -0,0 +1,228 @@
+use std::fmt;
+
+/// Why an operation on an icosphere or [`crate::levels::IcosphereLevels`] can't be done. Returned by
+/// the `try_` variants of methods that would otherwise panic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum IcosphereError {
+    /// Subdividing needs the previous icosphere to be exactly one binning depth below the current
+    /// one.
+    NonAdjacentDepths { previous: usize, current: usize },
+
+    /// The binning depth is too large for triangle and vertex counts to fit in a `usize`.
+    BinningDepthTooLarge {
+        binning_depth: usize,
+        max_binning_depth: usize,
+    },
+
+    /// The triangle index is not below the triangle count at this binning depth.
+    TriangleOutOfRange {
+        triangle_index: usize,
+        triangle_count: usize,
+    },
+
+    /// The triangle is in range, but hasn't been generated in a sparse icosphere.
+    TriangleNotGenerated { triangle_index: usize },
+
+    /// Levels need at least one level.
+    ZeroLevelCount,
+
+    /// Levels need the binning depth to increase by at least one per level.
+    ZeroBinningDepthStep,
+
+    /// The level is not below the level count.
+    LevelOutOfRange { level: usize, level_count: usize },
+
+    /// The chunk index is not below the chunk count of its level.
+    ChunkOutOfRange {
+        level: usize,
+        chunk_index: usize,
+        chunk_count: usize,
+    },
+
+    /// The canonical ID is not below the vertex count at [`crate::MAX_BINNING_DEPTH`], so no vertex
+    /// has it.
+    CanonicalIdOutOfRange {
+        canonical_id: usize,
+        vertex_count: usize,
+    },
+}
+
+impl fmt::Display for IcosphereError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IcosphereError::NonAdjacentDepths { previous, current } => write!(
+                f,
+                "can't subdivide from binning depth {previous} to binning depth {current}, which aren't adjacent"
+            ),
+            IcosphereError::BinningDepthTooLarge {
+                binning_depth,
+                max_binning_depth,
+            } => write!(
+                f,
+                "binning depth {binning_depth} is larger than the maximum of {max_binning_depth}"
+            ),
+            IcosphereError::TriangleOutOfRange {
+                triangle_index,
+                triangle_count,
+            } => write!(
+                f,
+                "triangle index {triangle_index} is out of range for {triangle_count} triangles"
+            ),
+            IcosphereError::TriangleNotGenerated { triangle_index } => {
+                write!(f, "triangle {triangle_index} isn't generated")
+            }
+            IcosphereError::ZeroLevelCount => write!(f, "the level count must be at least one"),
+            IcosphereError::ZeroBinningDepthStep => {
+                write!(f, "the binning depth step must be at least one")
+            }
+            IcosphereError::LevelOutOfRange { level, level_count } => {
+                write!(f, "level {level} is out of range for {level_count} levels")
+            }
+            IcosphereError::ChunkOutOfRange {
+                level,
+                chunk_index,
+                chunk_count,
+            } => write!(
+                f,
+                "chunk index {chunk_index} is out of range for {chunk_count} chunks at level {level}"
+            ),
+            IcosphereError::CanonicalIdOutOfRange {
+                canonical_id,
+                vertex_count,
+            } => write!(
+                f,
+                "canonical ID {canonical_id} is out of range for {vertex_count} vertices"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for IcosphereError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::levels::IcosphereLevels;
+    use crate::{Icosphere, MAX_BINNING_DEPTH, SparseIcosphere, triangle_count};
+    use glam::Vec3;
+
+    type Levels = IcosphereLevels<Vec3, SparseIcosphere<Vec3>>;
+
+    #[test]
+    fn try_triangle_errors() {
+        let mut icosphere = SparseIcosphere::<Vec3>::empty(1);
+        icosphere.subdivide_chunk(&SparseIcosphere::filled(0), 3);
+
+        assert_eq!(icosphere.try_triangle(12), Ok(icosphere.triangle(12)));
+        assert_eq!(
+            icosphere.try_triangle(80),
+            Err(IcosphereError::TriangleOutOfRange {
+                triangle_index: 80,
+                triangle_count: 80,
+            })
+        );
+        assert_eq!(
+            icosphere.try_triangle(0),
+            Err(IcosphereError::TriangleNotGenerated { triangle_index: 0 })
+        );
+    }
+
+    #[test]
+    fn try_subdivide_chunk_errors() {
+        let previous = SparseIcosphere::<Vec3>::filled(0);
+        let mut sparse = SparseIcosphere::empty(1);
+        sparse.subdivide_chunk(&previous, 3);
+
+        assert_eq!(
+            SparseIcosphere::<Vec3>::empty(2).try_subdivide_chunk(&previous, 0),
+            Err(IcosphereError::NonAdjacentDepths {
+                previous: 0,
+                current: 2,
+            })
+        );
+        assert_eq!(
+            SparseIcosphere::<Vec3>::empty(1).try_subdivide_chunk(&previous, 20),
+            Err(IcosphereError::TriangleOutOfRange {
+                triangle_index: 20,
+                triangle_count: 20,
+            })
+        );
+        assert_eq!(
+            SparseIcosphere::<Vec3>::empty(2).try_subdivide_chunk(&sparse, 0),
+            Err(IcosphereError::TriangleNotGenerated { triangle_index: 0 })
+        );
+        assert_eq!(
+            SparseIcosphere::<Vec3>::empty(2).try_subdivide_chunk(&sparse, 12),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn try_new_errors() {
+        assert_eq!(
+            Levels::try_new(1, 0, 1).err(),
+            Some(IcosphereError::ZeroLevelCount)
+        );
+        assert_eq!(
+            Levels::try_new(1, 2, 0).err(),
+            Some(IcosphereError::ZeroBinningDepthStep)
+        );
+        assert_eq!(
+            Levels::try_new(MAX_BINNING_DEPTH - 2, 2, 3).err(),
+            Some(IcosphereError::BinningDepthTooLarge {
+                binning_depth: MAX_BINNING_DEPTH + 1,
+                max_binning_depth: MAX_BINNING_DEPTH,
+            })
+        );
+        // Overflowing depths saturate instead of wrapping around to a small depth
+        assert_eq!(
+            Levels::try_new(1, 3, usize::MAX).err(),
+            Some(IcosphereError::BinningDepthTooLarge {
+                binning_depth: usize::MAX,
+                max_binning_depth: MAX_BINNING_DEPTH,
+            })
+        );
+        assert!(Levels::try_new(1, 2, 1).is_ok());
+    }
+
+    #[test]
+    fn try_update_chunk_errors() {
+        let mut levels = Levels::new(1, 2, 1);
+
+        assert_eq!(
+            levels.try_update_chunk(2, 0),
+            Err(IcosphereError::LevelOutOfRange {
+                level: 2,
+                level_count: 2,
+            })
+        );
+        assert_eq!(
+            levels.try_update_chunk(1, triangle_count(1)),
+            Err(IcosphereError::ChunkOutOfRange {
+                level: 1,
+                chunk_index: triangle_count(1),
+                chunk_count: triangle_count(1),
+            })
+        );
+        assert_eq!(levels.try_update_chunk(1, 5), Ok(true));
+        assert_eq!(levels.try_update_chunk(1, 5), Ok(false));
+    }
+
+    #[test]
+    fn messages_name_the_values() {
+        let error = IcosphereError::ChunkOutOfRange {
+            level: 2,
+            chunk_index: 81,
+            chunk_count: 80,
+        };
+
+        assert_eq!(
+            error.to_string(),
+            "chunk index 81 is out of range for 80 chunks at level 2"
+        );
+        assert_eq!(
+            IcosphereError::TriangleNotGenerated { triangle_index: 7 }.to_string(),
+            "triangle 7 isn't generated"
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use std::io::{self, Write};
+
+use glam::Vec3;
+
+use crate::{Icosphere, IcosphereVertex, levels::IcosphereLevels};
+
+use super::{CompactMesh, ExportOptions};
+
+const GLB_MAGIC: u32 = 0x4654_6C67;
+const GLB_VERSION: u32 = 2;
+const CHUNK_TYPE_JSON: u32 = 0x4E4F_534A;
+const CHUNK_TYPE_BIN: u32 = 0x004E_4942;
+
+const COMPONENT_TYPE_FLOAT: u32 = 5126;
+const COMPONENT_TYPE_UNSIGNED_INT: u32 = 5125;
+const TARGET_ARRAY_BUFFER: u32 = 34962;
+const TARGET_ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+/// How the levels of an [`IcosphereLevels`] are arranged in the exported scene.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LevelsLayout {
+    /// Every level is its own node in the scene, named after its level.
+    SeparateMeshes,
+
+    /// The most detailed level is the only node in the scene, and the other levels are attached to
+    /// it as lower detail alternatives using the `MSFT_lod` extension. Viewers without support for
+    /// the extension show the most detailed level.
+    Lod,
+}
+
+/// Writes the generated triangles of an icosphere as a binary glTF 2.0 file.
+///
+/// Normals, texture coordinates and custom vertex attributes are written if they are enabled in the
+/// options. Custom vertex attributes are named with a leading underscore, as the specification
+/// requires for application-specific attributes. Face attributes are ignored.
+///
+/// If the icosphere has no generated triangles, the file has no scene, since glTF doesn't allow empty
+/// meshes.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] before writing anything if a custom attribute name is
+/// invalid. See [`ExportOptions::validate`].
+pub fn write_glb<T, S, W>(ico: &S, options: &ExportOptions<T>, writer: W) -> io::Result<()>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+    W: Write,
+{
+    options.validate()?;
+
+    let mut builder = GlbBuilder::default();
+
+    if let Some(mesh) = builder.push_mesh(ico, options, "icosphere") {
+        builder.push_node(mesh, "icosphere", None);
+    }
+
+    builder.finish(writer)
+}
+
+/// Writes every level of an [`IcosphereLevels`] as a binary glTF 2.0 file. Levels with no generated
+/// triangles are skipped, and if no level has any, the file has no scene. See [`write_glb`].
+pub fn write_glb_levels<T, S, W>(
+    levels: &IcosphereLevels<T, S>,
+    options: &ExportOptions<T>,
+    layout: LevelsLayout,
+    writer: W,
+) -> io::Result<()>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+    W: Write,
+{
+    options.validate()?;
+
+    let mut builder = GlbBuilder::default();
+    let mut meshes = Vec::new();
+
+    for level in 0..levels.level_count() {
+        let name = format!("level {level}");
+
+        if let Some(mesh) = builder.push_mesh(levels.get(level), options, &name) {
+            meshes.push((mesh, name));
+        }
+    }
+
+    match layout {
+        LevelsLayout::SeparateMeshes => {
+            for (mesh, name) in meshes {
+                builder.push_node(mesh, &name, None);
+            }
+        }
+        LevelsLayout::Lod => {
+            // MSFT_lod lists alternatives from the most to the least detailed
+            if let Some((mesh, name)) = meshes.pop() {
+                let lod_nodes = meshes
+                    .into_iter()
+                    .rev()
+                    .map(|(mesh, name)| builder.push_lod_node(mesh, &name))
+                    .collect();
+
+                builder.push_node(mesh, &name, Some(lod_nodes));
+            }
+        }
+    }
+
+    builder.finish(writer)
+}
+
+/// Accumulates the JSON objects and the binary buffer of a GLB file.
+#[derive(Default)]
+struct GlbBuilder {
+    buffer: Vec<u8>,
+    buffer_views: Vec<String>,
+    accessors: Vec<String>,
+    meshes: Vec<String>,
+    nodes: Vec<String>,
+
+    /// Nodes that are directly in the scene, as opposed to LOD alternatives.
+    scene_nodes: Vec<usize>,
+    uses_lod: bool,
+}
+
+impl GlbBuilder {
+    /// Adds a mesh with one primitive. Returns its index, or `None` if the icosphere has no generated
+    /// triangles, since glTF doesn't allow empty accessors.
+    fn push_mesh<T, S>(&mut self, ico: &S, options: &ExportOptions<T>, name: &str) -> Option<usize>
+    where
+        T: IcosphereVertex,
+        S: Icosphere<T>,
+    {
+        let mesh = CompactMesh::new(ico);
+
+        if mesh.indices.is_empty() {
+            return None;
+        }
+
+        let vertices = ico.vertices();
+        let positions = mesh.positions(vertices);
+
+        let mut attributes = Vec::new();
+
+        let min = positions.iter().copied().fold(Vec3::INFINITY, Vec3::min);
+        let max = positions
+            .iter()
+            .copied()
+            .fold(Vec3::NEG_INFINITY, Vec3::max);
+        let position_accessor = self.push_accessor(
+            &positions
+                .iter()
+                .flat_map(|p| p.to_array())
+                .collect::<Vec<_>>(),
+            "VEC3",
+            Some((min, max)),
+        );
+        attributes.push(format!("\"POSITION\":{position_accessor}"));
+
+        if options.normals {
+            let normals: Vec<f32> = mesh
+                .normals(vertices)
+                .iter()
+                .flat_map(|n| n.to_array())
+                .collect();
+
+            let normal_accessor = self.push_accessor(&normals, "VEC3", None);
+            attributes.push(format!("\"NORMAL\":{normal_accessor}"));
+        }
+
+        if let Some(texcoords) = options.texcoords {
+            let texcoords: Vec<f32> = mesh
+                .vertices
+                .iter()
+                .flat_map(|&vertex_index| texcoords(&vertices[vertex_index]).to_array())
+                .collect();
+
+            let texcoord_accessor = self.push_accessor(&texcoords, "VEC2", None);
+            attributes.push(format!("\"TEXCOORD_0\":{texcoord_accessor}"));
+        }
+
+        if options.parent_positions {
+            let parent_positions = ico.parent_positions();
+            let parent_positions: Vec<f32> = mesh
+                .vertices
+                .iter()
+                .flat_map(|&vertex_index| parent_positions[vertex_index].to_array())
+                .collect();
+
+            let parent_position_accessor = self.push_accessor(&parent_positions, "VEC3", None);
+            attributes.push(format!("\"_PARENT_POSITION\":{parent_position_accessor}"));
+        }
+
+        for (attribute_name, attribute) in &options.vertex_attributes {
+            let values: Vec<f32> = mesh
+                .vertices
+                .iter()
+                .map(|&vertex_index| attribute(&vertices[vertex_index]))
+                .collect();
+
+            let accessor = self.push_accessor(&values, "SCALAR", None);
+            attributes.push(format!("\"_{}\":{accessor}", escape(attribute_name)));
+        }
+
+        let indices: Vec<u32> = mesh.indices.iter().flatten().copied().collect();
+        let indices_accessor = self.push_indices(&indices);
+
+        self.meshes.push(format!(
+            "{{\"name\":\"{}\",\"primitives\":[{{\"attributes\":{{{}}},\"indices\":{indices_accessor},\"mode\":4}}]}}",
+            escape(name),
+            attributes.join(","),
+        ));
+
+        Some(self.meshes.len() - 1)
+    }
+
+    /// Adds a node that is part of the scene, optionally with lower detail alternatives.
+    fn push_node(&mut self, mesh: usize, name: &str, lod_nodes: Option<Vec<usize>>) {
+        let extensions = match lod_nodes {
+            Some(lod_nodes) => {
+                self.uses_lod = true;
+
+                let ids: Vec<String> = lod_nodes.iter().map(usize::to_string).collect();
+                format!(
+                    ",\"extensions\":{{\"MSFT_lod\":{{\"ids\":[{}]}}}}",
+                    ids.join(",")
+                )
+            }
+            None => String::new(),
+        };
+
+        self.nodes.push(format!(
+            "{{\"name\":\"{}\",\"mesh\":{mesh}{extensions}}}",
+            escape(name)
+        ));
+        self.scene_nodes.push(self.nodes.len() - 1);
+    }
+
+    /// Adds a node that is only referenced by `MSFT_lod`. Returns its index.
+    fn push_lod_node(&mut self, mesh: usize, name: &str) -> usize {
+        self.nodes
+            .push(format!("{{\"name\":\"{}\",\"mesh\":{mesh}}}", escape(name)));
+
+        self.nodes.len() - 1
+    }
+
+    /// Adds a float accessor and its buffer view. Returns the accessor index.
+    fn push_accessor(&mut self, values: &[f32], kind: &str, bounds: Option<(Vec3, Vec3)>) -> usize {
+        let components = match kind {
+            "VEC3" => 3,
+            "VEC2" => 2,
+            _ => 1,
+        };
+
+        let buffer_view = self.push_buffer_view(bytemuck::cast_slice(values), TARGET_ARRAY_BUFFER);
+
+        let bounds = match bounds {
+            Some((min, max)) => format!(
+                ",\"min\":[{},{},{}],\"max\":[{},{},{}]",
+                min.x, min.y, min.z, max.x, max.y, max.z
+            ),
+            None => String::new(),
+        };
+
+        self.accessors.push(format!(
+            "{{\"bufferView\":{buffer_view},\"componentType\":{COMPONENT_TYPE_FLOAT},\"count\":{},\"type\":\"{kind}\"{bounds}}}",
+            values.len() / components,
+        ));
+
+        self.accessors.len() - 1
+    }
+
+    /// Adds an index accessor and its buffer view. Returns the accessor index.
+    fn push_indices(&mut self, indices: &[u32]) -> usize {
+        let buffer_view =
+            self.push_buffer_view(bytemuck::cast_slice(indices), TARGET_ELEMENT_ARRAY_BUFFER);
+
+        self.accessors.push(format!(
+            "{{\"bufferView\":{buffer_view},\"componentType\":{COMPONENT_TYPE_UNSIGNED_INT},\"count\":{},\"type\":\"SCALAR\"}}",
+            indices.len(),
+        ));
+
+        self.accessors.len() - 1
+    }
+
+    /// Appends data to the binary buffer. Returns the buffer view index.
+    fn push_buffer_view(&mut self, bytes: &[u8], target: u32) -> usize {
+        // Accessors must be aligned to their component size
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+
+        let offset = self.buffer.len();
+        self.buffer.extend_from_slice(bytes);
+
+        self.buffer_views.push(format!(
+            "{{\"buffer\":0,\"byteOffset\":{offset},\"byteLength\":{},\"target\":{target}}}",
+            bytes.len(),
+        ));
+
+        self.buffer_views.len() - 1
+    }
+
+    fn finish<W: Write>(mut self, mut writer: W) -> io::Result<()> {
+        self.buffer.resize(self.buffer.len().next_multiple_of(4), 0);
+
+        let scene_nodes: Vec<String> = self.scene_nodes.iter().map(usize::to_string).collect();
+        let extensions_used = if self.uses_lod {
+            ",\"extensionsUsed\":[\"MSFT_lod\"]"
+        } else {
+            ""
+        };
+
+        // glTF requires at least one item in every array and one byte in a buffer, so everything is
+        // left out when there is nothing to put in it, leaving a valid file with no scene
+        let scenes = if scene_nodes.is_empty() {
+            String::new()
+        } else {
+            format!(
+                ",\"scene\":0,\"scenes\":[{{\"nodes\":[{}]}}]",
+                scene_nodes.join(",")
+            )
+        };
+        let buffers = if self.buffer.is_empty() {
+            String::new()
+        } else {
+            format!(",\"buffers\":[{{\"byteLength\":{}}}]", self.buffer.len())
+        };
+
+        let mut json = format!(
+            "{{\"asset\":{{\"version\":\"2.0\",\"generator\":\"icosphere\"}}{extensions_used}{scenes}{}{}{}{}{buffers}}}",
+            json_array("nodes", &self.nodes),
+            json_array("meshes", &self.meshes),
+            json_array("accessors", &self.accessors),
+            json_array("bufferViews", &self.buffer_views),
+        )
+        .into_bytes();
+
+        // The JSON chunk is padded with spaces
+        json.resize(json.len().next_multiple_of(4), b' ');
+
+        let mut length = 12 + 8 + json.len();
+        if !self.buffer.is_empty() {
+            length += 8 + self.buffer.len();
+        }
+
+        writer.write_all(&GLB_MAGIC.to_le_bytes())?;
+        writer.write_all(&GLB_VERSION.to_le_bytes())?;
+        writer.write_all(&(length as u32).to_le_bytes())?;
+
+        writer.write_all(&(json.len() as u32).to_le_bytes())?;
+        writer.write_all(&CHUNK_TYPE_JSON.to_le_bytes())?;
+        writer.write_all(&json)?;
+
+        if !self.buffer.is_empty() {
+            writer.write_all(&(self.buffer.len() as u32).to_le_bytes())?;
+            writer.write_all(&CHUNK_TYPE_BIN.to_le_bytes())?;
+            writer.write_all(&self.buffer)?;
+        }
+
+        writer.flush()
+    }
+}
+
+/// A property holding a JSON array of the given objects, preceded by a comma, or nothing if there are
+/// no objects.
+fn json_array(name: &str, objects: &[String]) -> String {
+    if objects.is_empty() {
+        String::new()
+    } else {
+        format!(",\"{name}\":[{}]", objects.join(","))
+    }
+}
+
+/// Escapes a string for use inside a JSON string literal.
+fn escape(string: &str) -> String {
+    let mut escaped = String::with_capacity(string.len());
+
+    for character in string.chars() {
+        match character {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            character if character.is_control() => {
+                escaped.push_str(&format!("\\u{:04x}", character as u32))
+            }
+            character => escaped.push(character),
+        }
+    }
+
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere};
+
+    /// The JSON chunk of a GLB file, without padding.
+    fn json_chunk(glb: &[u8]) -> String {
+        let length = u32::from_le_bytes(glb[12..16].try_into().unwrap()) as usize;
+        String::from_utf8(glb[20..20 + length].to_vec())
+            .unwrap()
+            .trim_end()
+            .to_owned()
+    }
+
+    #[test]
+    fn empty_icosphere_has_no_empty_arrays() {
+        let ico = SparseIcosphere::<Vec3>::empty(2);
+        let mut glb = Vec::new();
+        write_glb(&ico, &ExportOptions::new(), &mut glb).unwrap();
+
+        assert_eq!(
+            json_chunk(&glb),
+            "{\"asset\":{\"version\":\"2.0\",\"generator\":\"icosphere\"}}"
+        );
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+    }
+
+    #[test]
+    fn icosphere_is_in_the_scene() {
+        let ico = StaticIcosphere::<Vec3>::regular();
+        let mut glb = Vec::new();
+        write_glb(&ico, &ExportOptions::new(), &mut glb).unwrap();
+
+        let json = json_chunk(&glb);
+        assert!(json.contains("\"scene\":0,\"scenes\":[{\"nodes\":[0]}]"));
+        assert!(json.contains("\"buffers\":[{\"byteLength\":"));
+        assert_eq!(
+            u32::from_le_bytes(glb[8..12].try_into().unwrap()) as usize,
+            glb.len()
+        );
+    }
+
+    #[test]
+    fn attribute_named_like_the_parent_positions_is_rejected() {
+        let ico = StaticIcosphere::<Vec3>::regular();
+        let height = |vertex: &Vec3| vertex.y;
+        let mut options = ExportOptions::new().with_parent_positions();
+        options.vertex_attributes.push(("PARENT_POSITION", &height));
+
+        let mut glb = Vec::new();
+        let error = write_glb(&ico, &options, &mut glb).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(glb.is_empty());
+    }
+}
@@ -0,0 +1,241 @@
+use std::{collections::HashMap, fmt, io};
+
+use glam::{Vec2, Vec3};
+
+use crate::{Icosphere, IcosphereVertex};
+
+pub mod gltf;
+pub mod obj;
+pub mod ply;
+
+/// A custom per-vertex attribute, computed from the vertex.
+pub type VertexAttribute<'a, T> = (&'a str, &'a dyn Fn(&T) -> f32);
+
+/// A custom per-face attribute, computed from the triangle index.
+pub type FaceAttribute<'a> = (&'a str, &'a dyn Fn(usize) -> f32);
+
+/// What to write besides positions and faces.
+pub struct ExportOptions<'a, T: IcosphereVertex> {
+    /// Whether to write vertex normals. See [`IcosphereVertex::surface_normal`].
+    pub normals: bool,
+
+    /// Texture coordinates computed for every vertex, if any.
+    pub texcoords: Option<&'a dyn Fn(&T) -> Vec2>,
+
+    /// Whether to write the position of every vertex on the previous binning depth, for morphing
+    /// between levels. See [`Icosphere::parent_positions`]. That's only the previous level of
+    /// [`crate::levels::IcosphereLevels`] if its `binning_depth_step` is 1.
+    ///
+    /// Only formats with custom properties (PLY and glTF) write these.
+    pub parent_positions: bool,
+
+    /// Extra scalar properties written for every vertex. Names must be valid, see
+    /// [`InvalidAttributeName`].
+    ///
+    /// Only formats with custom properties (PLY and glTF) write these.
+    pub vertex_attributes: Vec<VertexAttribute<'a, T>>,
+
+    /// Extra scalar properties written for every face. Names must be valid, see
+    /// [`InvalidAttributeName`].
+    ///
+    /// Only formats with custom properties (PLY) write these.
+    pub face_attributes: Vec<FaceAttribute<'a>>,
+}
+
+impl<'a, T: IcosphereVertex> ExportOptions<'a, T> {
+    /// Positions and faces only.
+    pub fn new() -> Self {
+        Self {
+            normals: false,
+            texcoords: None,
+            parent_positions: false,
+            vertex_attributes: Vec::new(),
+            face_attributes: Vec::new(),
+        }
+    }
+
+    /// Also write vertex normals.
+    pub fn with_normals(mut self) -> Self {
+        self.normals = true;
+        self
+    }
+
+    /// Also write texture coordinates.
+    pub fn with_texcoords(mut self, texcoords: &'a dyn Fn(&T) -> Vec2) -> Self {
+        self.texcoords = Some(texcoords);
+        self
+    }
+
+    /// Also write the positions of vertices on the previous binning depth.
+    pub fn with_parent_positions(mut self) -> Self {
+        self.parent_positions = true;
+        self
+    }
+
+    /// Also write a custom per-vertex attribute. Fails if the name is invalid, see
+    /// [`InvalidAttributeName`].
+    pub fn with_vertex_attribute(
+        mut self,
+        name: &'a str,
+        attribute: &'a dyn Fn(&T) -> f32,
+    ) -> Result<Self, InvalidAttributeName> {
+        validate_attribute_name(name, RESERVED_VERTEX_NAMES, &self.vertex_attributes)?;
+
+        self.vertex_attributes.push((name, attribute));
+        Ok(self)
+    }
+
+    /// Also write a custom per-face attribute. Fails if the name is invalid, see
+    /// [`InvalidAttributeName`].
+    pub fn with_face_attribute(
+        mut self,
+        name: &'a str,
+        attribute: &'a dyn Fn(usize) -> f32,
+    ) -> Result<Self, InvalidAttributeName> {
+        validate_attribute_name(name, RESERVED_FACE_NAMES, &self.face_attributes)?;
+
+        self.face_attributes.push((name, attribute));
+        Ok(self)
+    }
+
+    /// Checks the names of every custom attribute, including ones pushed to the fields directly.
+    pub fn validate(&self) -> Result<(), InvalidAttributeName> {
+        for (i, &(name, _)) in self.vertex_attributes.iter().enumerate() {
+            validate_attribute_name(name, RESERVED_VERTEX_NAMES, &self.vertex_attributes[..i])?;
+        }
+
+        for (i, &(name, _)) in self.face_attributes.iter().enumerate() {
+            validate_attribute_name(name, RESERVED_FACE_NAMES, &self.face_attributes[..i])?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Names of the vertex properties the exporters write themselves. glTF prefixes custom names with an
+/// underscore, so `PARENT_POSITION` would clash with its `_PARENT_POSITION`.
+const RESERVED_VERTEX_NAMES: &[&str] = &[
+    "x",
+    "y",
+    "z",
+    "nx",
+    "ny",
+    "nz",
+    "s",
+    "t",
+    "parent_x",
+    "parent_y",
+    "parent_z",
+    "PARENT_POSITION",
+];
+
+/// Names of the face properties the exporters write themselves.
+const RESERVED_FACE_NAMES: &[&str] = &["vertex_indices"];
+
+/// A custom attribute name that is empty, contains whitespace, is used by another attribute of the
+/// same kind, or is the name of a property the exporters write themselves (like `x` or
+/// `vertex_indices`). Names are written as single words in PLY headers, so these would produce a
+/// corrupt or ambiguous file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidAttributeName(pub String);
+
+impl fmt::Display for InvalidAttributeName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "invalid attribute name {:?}, names must be non-empty, contain no whitespace, and not \
+             be used by another property",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for InvalidAttributeName {}
+
+impl From<InvalidAttributeName> for io::Error {
+    fn from(error: InvalidAttributeName) -> Self {
+        io::Error::new(io::ErrorKind::InvalidInput, error)
+    }
+}
+
+/// Checks a name against the reserved names and the attributes before it.
+fn validate_attribute_name<F>(
+    name: &str,
+    reserved: &[&str],
+    previous: &[(&str, F)],
+) -> Result<(), InvalidAttributeName> {
+    if name.is_empty()
+        || name.contains(char::is_whitespace)
+        || reserved.contains(&name)
+        || previous.iter().any(|&(previous, _)| previous == name)
+    {
+        Err(InvalidAttributeName(name.to_owned()))
+    } else {
+        Ok(())
+    }
+}
+
+impl<T: IcosphereVertex> Default for ExportOptions<'_, T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The generated part of an icosphere, with vertex indices remapped so that only vertices used by
+/// a generated triangle are kept.
+#[derive(Debug, Clone)]
+pub struct CompactMesh {
+    /// For each compacted vertex, its index in [`Icosphere::vertices`].
+    pub vertices: Vec<usize>,
+
+    /// For each compacted triangle, its index in the icosphere.
+    pub triangles: Vec<usize>,
+
+    /// Triangles that index into [`Self::vertices`].
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl CompactMesh {
+    /// Collects every generated triangle of the icosphere, in ascending triangle index order. Vertices
+    /// are numbered in order of first use.
+    pub fn new<T: IcosphereVertex, S: Icosphere<T>>(ico: &S) -> Self {
+        let triangles = ico.allocated_triangle_indices();
+
+        let mut vertices = Vec::new();
+        let mut compacted_vertices = HashMap::new();
+        let mut indices = Vec::with_capacity(triangles.len());
+
+        for &triangle_index in &triangles {
+            let triangle = ico.triangle(triangle_index).map(|vertex_index| {
+                *compacted_vertices.entry(vertex_index).or_insert_with(|| {
+                    vertices.push(vertex_index as usize);
+                    vertices.len() as u32 - 1
+                })
+            });
+
+            indices.push(triangle);
+        }
+
+        Self {
+            vertices,
+            triangles,
+            indices,
+        }
+    }
+
+    /// Positions of the compacted vertices. See [`IcosphereVertex::surface_position`].
+    pub fn positions<T: IcosphereVertex>(&self, vertices: &[T]) -> Vec<Vec3> {
+        self.vertices
+            .iter()
+            .map(|&vertex_index| vertices[vertex_index].surface_position())
+            .collect()
+    }
+
+    /// Normals of the compacted vertices. See [`IcosphereVertex::surface_normal`].
+    pub fn normals<T: IcosphereVertex>(&self, vertices: &[T]) -> Vec<Vec3> {
+        self.vertices
+            .iter()
+            .map(|&vertex_index| vertices[vertex_index].surface_normal())
+            .collect()
+    }
+}
@@ -0,0 +1,52 @@
+use std::io::{self, Write};
+
+use crate::{Icosphere, IcosphereVertex};
+
+use super::{CompactMesh, ExportOptions};
+
+/// Writes the generated triangles of an icosphere as a Wavefront OBJ file.
+///
+/// OBJ has no way to store custom properties, so [`ExportOptions::vertex_attributes`] and
+/// [`ExportOptions::face_attributes`] are ignored.
+pub fn write_obj<T, S, W>(ico: &S, options: &ExportOptions<T>, mut writer: W) -> io::Result<()>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+    W: Write,
+{
+    let mesh = CompactMesh::new(ico);
+    let positions = mesh.positions(ico.vertices());
+
+    writeln!(writer, "# icosphere, binning depth {}", ico.binning_depth())?;
+
+    for position in &positions {
+        writeln!(writer, "v {} {} {}", position.x, position.y, position.z)?;
+    }
+
+    if options.normals {
+        for normal in mesh.normals(ico.vertices()) {
+            writeln!(writer, "vn {} {} {}", normal.x, normal.y, normal.z)?;
+        }
+    }
+
+    if let Some(texcoords) = options.texcoords {
+        for &vertex_index in &mesh.vertices {
+            let texcoord = texcoords(&ico.vertices()[vertex_index]);
+            writeln!(writer, "vt {} {}", texcoord.x, texcoord.y)?;
+        }
+    }
+
+    for triangle in &mesh.indices {
+        // OBJ indices start at one
+        let [a, b, c] = triangle.map(|vertex_index| vertex_index + 1);
+
+        match (options.texcoords.is_some(), options.normals) {
+            (false, false) => writeln!(writer, "f {a} {b} {c}")?,
+            (false, true) => writeln!(writer, "f {a}//{a} {b}//{b} {c}//{c}")?,
+            (true, false) => writeln!(writer, "f {a}/{a} {b}/{b} {c}/{c}")?,
+            (true, true) => writeln!(writer, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?,
+        }
+    }
+
+    writer.flush()
+}
@@ -0,0 +1,276 @@
+use std::io::{self, Write};
+
+use crate::{Icosphere, IcosphereVertex};
+
+use super::{CompactMesh, ExportOptions};
+
+/// How the body of a PLY file is encoded. The header is always ASCII.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+}
+
+/// Writes the generated triangles of an icosphere as a PLY file, including any custom attributes.
+///
+/// Fails with [`io::ErrorKind::InvalidInput`] before writing anything if a custom attribute name is
+/// invalid. See [`ExportOptions::validate`].
+pub fn write_ply<T, S, W>(
+    ico: &S,
+    options: &ExportOptions<T>,
+    format: PlyFormat,
+    mut writer: W,
+) -> io::Result<()>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+    W: Write,
+{
+    options.validate()?;
+
+    let mesh = CompactMesh::new(ico);
+    let vertices = ico.vertices();
+
+    writeln!(writer, "ply")?;
+    writeln!(
+        writer,
+        "format {} 1.0",
+        match format {
+            PlyFormat::Ascii => "ascii",
+            PlyFormat::BinaryLittleEndian => "binary_little_endian",
+        }
+    )?;
+    writeln!(
+        writer,
+        "comment icosphere, binning depth {}",
+        ico.binning_depth()
+    )?;
+
+    writeln!(writer, "element vertex {}", mesh.vertices.len())?;
+    for property in ["x", "y", "z"] {
+        writeln!(writer, "property float {property}")?;
+    }
+    if options.normals {
+        for property in ["nx", "ny", "nz"] {
+            writeln!(writer, "property float {property}")?;
+        }
+    }
+    if options.texcoords.is_some() {
+        for property in ["s", "t"] {
+            writeln!(writer, "property float {property}")?;
+        }
+    }
+    if options.parent_positions {
+        for property in ["parent_x", "parent_y", "parent_z"] {
+            writeln!(writer, "property float {property}")?;
+        }
+    }
+    for (name, _) in &options.vertex_attributes {
+        writeln!(writer, "property float {name}")?;
+    }
+
+    writeln!(writer, "element face {}", mesh.indices.len())?;
+    writeln!(writer, "property list uchar uint vertex_indices")?;
+    for (name, _) in &options.face_attributes {
+        writeln!(writer, "property float {name}")?;
+    }
+
+    writeln!(writer, "end_header")?;
+
+    let parent_positions = options.parent_positions.then(|| ico.parent_positions());
+    let mut properties = Vec::new();
+
+    for &vertex_index in &mesh.vertices {
+        let vertex = &vertices[vertex_index];
+        properties.clear();
+        properties.extend(vertex.surface_position().to_array());
+        if options.normals {
+            properties.extend(vertex.surface_normal().to_array());
+        }
+        if let Some(texcoords) = options.texcoords {
+            properties.extend(texcoords(vertex).to_array());
+        }
+        if let Some(parent_positions) = &parent_positions {
+            properties.extend(parent_positions[vertex_index].to_array());
+        }
+        properties.extend(
+            options
+                .vertex_attributes
+                .iter()
+                .map(|(_, attribute)| attribute(vertex)),
+        );
+
+        match format {
+            PlyFormat::Ascii => write_ascii_row(&mut writer, &[], &properties)?,
+            PlyFormat::BinaryLittleEndian => write_binary_row(&mut writer, &[], &properties)?,
+        }
+    }
+
+    for (&triangle_index, triangle) in mesh.triangles.iter().zip(&mesh.indices) {
+        properties.clear();
+        properties.extend(
+            options
+                .face_attributes
+                .iter()
+                .map(|(_, attribute)| attribute(triangle_index)),
+        );
+
+        match format {
+            PlyFormat::Ascii => write_ascii_row(&mut writer, triangle, &properties)?,
+            PlyFormat::BinaryLittleEndian => write_binary_row(&mut writer, triangle, &properties)?,
+        }
+    }
+
+    writer.flush()
+}
+
+/// Writes one element. Vertex rows have no indices, face rows have a list of three.
+fn write_ascii_row<W: Write>(
+    writer: &mut W,
+    indices: &[u32],
+    properties: &[f32],
+) -> io::Result<()> {
+    let mut separator = "";
+
+    if !indices.is_empty() {
+        write!(writer, "{}", indices.len())?;
+        separator = " ";
+
+        for index in indices {
+            write!(writer, " {index}")?;
+        }
+    }
+
+    for property in properties {
+        write!(writer, "{separator}{property}")?;
+        separator = " ";
+    }
+
+    writeln!(writer)
+}
+
+/// Same as [`write_ascii_row`], but in little endian binary.
+fn write_binary_row<W: Write>(
+    writer: &mut W,
+    indices: &[u32],
+    properties: &[f32],
+) -> io::Result<()> {
+    if !indices.is_empty() {
+        writer.write_all(&[indices.len() as u8])?;
+
+        for index in indices {
+            writer.write_all(&index.to_le_bytes())?;
+        }
+    }
+
+    for property in properties {
+        writer.write_all(&property.to_le_bytes())?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{StaticIcosphere, export::InvalidAttributeName};
+
+    #[test]
+    fn attribute_names_with_whitespace_are_rejected() {
+        let height = |vertex: &Vec3| vertex.y;
+
+        for name in ["", "two words", "line\nbreak", "tab\t"] {
+            let result = ExportOptions::new().with_vertex_attribute(name, &height);
+            assert_eq!(result.err(), Some(InvalidAttributeName(name.to_owned())));
+        }
+
+        let area = |_| 1.0;
+        assert!(
+            ExportOptions::<Vec3>::new()
+                .with_face_attribute("end_header\nelement", &area)
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn reserved_and_repeated_attribute_names_are_rejected() {
+        let height = |vertex: &Vec3| vertex.y;
+        let area = |_| 1.0;
+
+        for name in ["x", "nz", "t", "parent_y", "PARENT_POSITION"] {
+            let result = ExportOptions::new().with_vertex_attribute(name, &height);
+            assert_eq!(result.err(), Some(InvalidAttributeName(name.to_owned())));
+        }
+
+        let result = ExportOptions::<Vec3>::new().with_face_attribute("vertex_indices", &area);
+        assert_eq!(
+            result.err(),
+            Some(InvalidAttributeName("vertex_indices".to_owned()))
+        );
+
+        let options = ExportOptions::new()
+            .with_vertex_attribute("height", &height)
+            .unwrap();
+        let result = options.with_vertex_attribute("height", &height);
+        assert_eq!(
+            result.err(),
+            Some(InvalidAttributeName("height".to_owned()))
+        );
+
+        // Vertex and face properties are separate elements, so they may share names
+        let options = ExportOptions::new()
+            .with_vertex_attribute("area", &height)
+            .unwrap()
+            .with_face_attribute("area", &area)
+            .unwrap()
+            .with_face_attribute("x", &area)
+            .unwrap();
+        assert!(options.validate().is_ok());
+    }
+
+    #[test]
+    fn repeated_attribute_pushed_directly_is_rejected() {
+        let area = |_| 1.0;
+        let mut options = ExportOptions::<Vec3>::new();
+        options.face_attributes.push(("area", &area));
+        assert!(options.validate().is_ok());
+
+        options.face_attributes.push(("area", &area));
+        assert_eq!(
+            options.validate(),
+            Err(InvalidAttributeName("area".to_owned()))
+        );
+    }
+
+    #[test]
+    fn invalid_attribute_pushed_directly_fails_before_writing() {
+        let ico = StaticIcosphere::<Vec3>::regular();
+        let height = |vertex: &Vec3| vertex.y;
+        let mut options = ExportOptions::new();
+        options.vertex_attributes.push(("two words", &height));
+
+        let mut bytes = Vec::new();
+        let error = write_ply(&ico, &options, PlyFormat::Ascii, &mut bytes).unwrap_err();
+
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(bytes.is_empty());
+    }
+
+    #[test]
+    fn header_lists_custom_attributes() {
+        let ico = StaticIcosphere::<Vec3>::regular();
+        let height = |vertex: &Vec3| vertex.y;
+        let options = ExportOptions::new()
+            .with_vertex_attribute("height", &height)
+            .unwrap();
+
+        let mut bytes = Vec::new();
+        write_ply(&ico, &options, PlyFormat::Ascii, &mut bytes).unwrap();
+        let text = String::from_utf8(bytes).unwrap();
+
+        assert!(text.contains("element vertex 12\n"));
+        assert!(text.contains("property float height\nelement face 20\n"));
+    }
+}
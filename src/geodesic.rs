@@ -0,0 +1,293 @@
+use std::{
+    collections::{HashMap, HashSet},
+    ops::Range,
+};
+
+use crate::{
+    ICOSAHEDRON_TRIANGLES, IcosphereVertex, icosahedron_positions, neighbors_from_triangles,
+};
+
+/// Vertex count of a geodesic sphere with the given frequency.
+pub fn geodesic_vertex_count(frequency: usize) -> usize {
+    10 * frequency * frequency + 2
+}
+
+/// Triangle count of a geodesic sphere with the given frequency.
+pub fn geodesic_triangle_count(frequency: usize) -> usize {
+    20 * frequency * frequency
+}
+
+/// A Class I geodesic sphere, made by splitting every edge of the regular icosahedron into `frequency`
+/// segments, filling each face with a triangular lattice, and normalizing the resulting coordinates.
+///
+/// Unlike [`crate::StaticIcosphere`], whose vertex count quadruples with every subdivision, the
+/// frequency can be any positive integer. A frequency of `2^n` has as many vertices as an icosphere
+/// with a binning depth of `n`, but their positions differ slightly, since the lattice is spaced
+/// evenly on the flat face instead of being subdivided after each normalization, and the triangles are
+/// ordered differently.
+///
+/// This doesn't implement [`crate::Icosphere`]: its triangles aren't split four ways from a parent
+/// triangle, so nothing built on the triangle hierarchy works on it, which includes
+/// [`crate::locate`], [`crate::adjacency`], ray casting, [`crate::levels::IcosphereLevels`], the
+/// exporters and caches. What does carry over is the mesh itself: [`Self::vertices`],
+/// [`Self::triangles`] and [`Self::neighbors`] have the same layout as the fields of
+/// [`crate::StaticIcosphere`], and the neighbors are built from the triangles the same way.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct GeodesicSphere<T: IcosphereVertex> {
+    /// The 12 vertices of the regular icosahedron come first, in the same order as in
+    /// [`crate::StaticIcosphere::regular`], followed by the vertices inside the edges of the
+    /// icosahedron, and then the vertices inside its faces.
+    pub vertices: Vec<T>,
+
+    /// Triangles are grouped by the face of the regular icosahedron they are in, so the triangles of
+    /// `face` are at [`Self::face_triangles`]`(face)`. Within a face, see [`Self::lattice_triangle`].
+    ///
+    /// When rendering, if chunks are necessary, treat these groups of triangles as chunks.
+    pub triangles: Vec<[u32; 3]>,
+
+    /// For each vertex, this contains the five or six neighbor vertices.
+    pub neighbors: HashMap<usize, HashSet<usize>>,
+
+    /// Number of segments each edge of the regular icosahedron is split into.
+    pub frequency: usize,
+}
+
+impl<T: IcosphereVertex> GeodesicSphere<T> {
+    /// Constructs the geodesic sphere with the given frequency. A frequency of one is the regular
+    /// icosahedron.
+    ///
+    /// The binning depth passed to [`IcosphereVertex::from_position`] is the smallest binning depth
+    /// of an icosphere with at least as many vertices.
+    pub fn new(frequency: usize) -> Self {
+        assert!(frequency > 0, "Geodesic sphere frequency must be positive");
+
+        let binning_depth = frequency.next_power_of_two().trailing_zeros() as usize;
+        let n = frequency;
+
+        let corners = icosahedron_positions();
+        let mut vertices: Vec<T> = corners
+            .iter()
+            .map(|&position| T::from_position(position, binning_depth))
+            .collect();
+
+        // Vertices inside each edge, keyed by the sorted corner indices, ordered from the lower corner
+        let mut edges: HashMap<(u32, u32), Vec<u32>> = HashMap::new();
+
+        for &[a, b, c] in &ICOSAHEDRON_TRIANGLES {
+            for (i, j) in [(a, b), (b, c), (c, a)] {
+                let key = if i > j { (j, i) } else { (i, j) };
+
+                if edges.contains_key(&key) {
+                    continue;
+                }
+
+                let (start, end) = (corners[key.0 as usize], corners[key.1 as usize]);
+                let edge_vertices = (1..n)
+                    .map(|step| {
+                        let position = start.lerp(end, step as f32 / n as f32).normalize();
+                        vertices.push(T::from_position(position, binning_depth));
+
+                        vertices.len() as u32 - 1
+                    })
+                    .collect();
+
+                edges.insert(key, edge_vertices);
+            }
+        }
+
+        let mut triangles = Vec::with_capacity(geodesic_triangle_count(n));
+
+        for &[a, b, c] in &ICOSAHEDRON_TRIANGLES {
+            let (pa, pb, pc) = (
+                corners[a as usize],
+                corners[b as usize],
+                corners[c as usize],
+            );
+
+            // Vertex at lattice coordinate (i, j), which is `a + (b - a) * i / n + (c - a) * j / n`
+            let mut lattice = vec![0u32; (n + 1) * (n + 1)];
+
+            for j in 0..=n {
+                for i in 0..=(n - j) {
+                    lattice[j * (n + 1) + i] = match (i, j) {
+                        (0, 0) => a,
+                        (i, 0) if i == n => b,
+                        (0, j) if j == n => c,
+                        (i, 0) => edge_vertex(&edges, a, b, i, n),
+                        (0, j) => edge_vertex(&edges, a, c, j, n),
+                        (i, j) if i + j == n => edge_vertex(&edges, b, c, j, n),
+                        (i, j) => {
+                            let position = (pa
+                                + (pb - pa) * (i as f32 / n as f32)
+                                + (pc - pa) * (j as f32 / n as f32))
+                                .normalize();
+                            vertices.push(T::from_position(position, binning_depth));
+
+                            vertices.len() as u32 - 1
+                        }
+                    };
+                }
+            }
+
+            let vertex = |i: usize, j: usize| lattice[j * (n + 1) + i];
+
+            for j in 0..n {
+                for i in 0..(n - j) {
+                    triangles.push([vertex(i, j), vertex(i + 1, j), vertex(i, j + 1)]);
+
+                    if i + 1 < n - j {
+                        triangles.push([vertex(i + 1, j), vertex(i + 1, j + 1), vertex(i, j + 1)]);
+                    }
+                }
+            }
+        }
+
+        let neighbors = neighbors_from_triangles(&triangles);
+
+        Self {
+            vertices,
+            triangles,
+            neighbors,
+            frequency,
+        }
+    }
+
+    /// The range of triangle indices inside the given face of the regular icosahedron.
+    pub fn face_triangles(&self, face: usize) -> Range<usize> {
+        let face_triangle_count = self.frequency * self.frequency;
+
+        face * face_triangle_count..(face + 1) * face_triangle_count
+    }
+
+    /// Index of a triangle from its position in the lattice of a face of the regular icosahedron.
+    ///
+    /// With the face's corners `[a, b, c]`, lattice coordinate `(i, j)` is at
+    /// `a + (b - a) * i / frequency + (c - a) * j / frequency` before normalization. The upward
+    /// triangle at `(i, j)` has the corners `(i, j)`, `(i + 1, j)` and `(i, j + 1)`, and requires
+    /// `i + j < frequency`. The downward triangle at `(i, j)` has the corners `(i + 1, j)`,
+    /// `(i + 1, j + 1)` and `(i, j + 1)`, and requires `i + j + 1 < frequency`.
+    pub fn lattice_triangle(&self, face: usize, i: usize, j: usize, upward: bool) -> usize {
+        let n = self.frequency;
+
+        // Each row `j` has `n - j` upward and `n - j - 1` downward triangles, interleaved
+        let row_start = j * (2 * n - j);
+        let offset = if upward { 2 * i } else { 2 * i + 1 };
+
+        self.face_triangles(face).start + row_start + offset
+    }
+}
+
+/// The vertex `step` segments away from corner `from` on the edge to corner `to`.
+fn edge_vertex(
+    edges: &HashMap<(u32, u32), Vec<u32>>,
+    from: u32,
+    to: u32,
+    step: usize,
+    n: usize,
+) -> u32 {
+    if from < to {
+        edges[&(from, to)][step - 1]
+    } else {
+        edges[&(to, from)][n - step - 1]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+
+    #[test]
+    fn counts_for_odd_frequencies() {
+        for frequency in [3, 5, 7] {
+            let sphere = GeodesicSphere::<Vec3>::new(frequency);
+
+            assert_eq!(sphere.vertices.len(), geodesic_vertex_count(frequency));
+            assert_eq!(sphere.triangles.len(), geodesic_triangle_count(frequency));
+            assert_eq!(sphere.neighbors.len(), geodesic_vertex_count(frequency));
+
+            // Euler's formula, with every edge counted from both ends
+            let edge_count: usize = sphere.neighbors.values().map(HashSet::len).sum::<usize>() / 2;
+            assert_eq!(
+                sphere.vertices.len() + sphere.triangles.len(),
+                edge_count + 2
+            );
+
+            let pentagons = sphere.neighbors.values().filter(|n| n.len() == 5).count();
+            assert_eq!(pentagons, 12);
+            assert!(
+                sphere
+                    .neighbors
+                    .values()
+                    .all(|n| n.len() == 5 || n.len() == 6)
+            );
+        }
+    }
+
+    #[test]
+    fn face_triangles_partition_the_triangles() {
+        let sphere = GeodesicSphere::<Vec3>::new(5);
+
+        for face in 0..20 {
+            let triangles = sphere.face_triangles(face);
+
+            assert_eq!(triangles.len(), 25);
+            assert_eq!(triangles.start, face * 25);
+        }
+
+        assert_eq!(sphere.face_triangles(19).end, sphere.triangles.len());
+    }
+
+    #[test]
+    fn lattice_triangle_corners() {
+        let corners = icosahedron_positions();
+
+        for frequency in [3, 5, 7] {
+            let sphere = GeodesicSphere::<Vec3>::new(frequency);
+            let n = frequency as f32;
+
+            for (face, &[a, b, c]) in ICOSAHEDRON_TRIANGLES.iter().enumerate() {
+                let (pa, pb, pc) = (
+                    corners[a as usize],
+                    corners[b as usize],
+                    corners[c as usize],
+                );
+                let lattice = |i: usize, j: usize| {
+                    (pa + (pb - pa) * (i as f32 / n) + (pc - pa) * (j as f32 / n)).normalize()
+                };
+
+                let mut visited = HashSet::new();
+
+                for j in 0..frequency {
+                    for i in 0..frequency - j {
+                        let mut expected =
+                            vec![(true, [lattice(i, j), lattice(i + 1, j), lattice(i, j + 1)])];
+                        if i + j + 1 < frequency {
+                            expected.push((
+                                false,
+                                [lattice(i + 1, j), lattice(i + 1, j + 1), lattice(i, j + 1)],
+                            ));
+                        }
+
+                        for (upward, expected) in expected {
+                            let triangle_index = sphere.lattice_triangle(face, i, j, upward);
+                            assert!(sphere.face_triangles(face).contains(&triangle_index));
+                            assert!(visited.insert(triangle_index));
+
+                            let positions = sphere.triangles[triangle_index]
+                                .map(|vertex_index| sphere.vertices[vertex_index as usize]);
+
+                            for (position, expected) in positions.into_iter().zip(expected) {
+                                assert!(position.distance(expected) < 1e-5);
+                            }
+                        }
+                    }
+                }
+
+                assert_eq!(visited.len(), frequency * frequency);
+            }
+        }
+    }
+}
@@ -1,11 +1,21 @@
 use std::{marker::PhantomData, ops::Range};
 
-use crate::{Icosphere, IcosphereVertex, triangle_count};
+use glam::Vec3;
+
+use crate::{
+    Icosphere, IcosphereVertex, MAX_BINNING_DEPTH,
+    error::IcosphereError,
+    locate,
+    midpoint::MidpointRule,
+    raycast::{self, RayHit},
+    triangle_count,
+};
 
 /// A collection of icosphere subdivisions, which can be used for rendering, similar to LODs.
 /// We use terminology "levels", because LOD usually makes the mesh less detailed as it increases,
 /// while this tesselates the mesh into more detailed shapes as the binning depth increases.  
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct IcosphereLevels<T, S>
 where
     T: IcosphereVertex,
@@ -40,38 +50,303 @@ where
     S: Icosphere<T>,
 {
     /// Constructs all necessary icospheres given the minimum depth, the level count, and the depth step.
-    /// The icospheres will be potentially empty/not generated yet.
+    /// The icospheres will be potentially empty/not generated yet, except for the 0th level, which is
+    /// always filled because there is nothing below it to subdivide from.
+    ///
+    /// Panics if the arguments are invalid. See [`Self::try_new`].
     pub fn new(min_binning_depth: usize, level_count: usize, binning_depth_step: usize) -> Self {
-        let max_binning_depth = min_binning_depth + (level_count - 1) * binning_depth_step;
+        Self::new_with(
+            min_binning_depth,
+            level_count,
+            binning_depth_step,
+            MidpointRule::Normalized,
+        )
+    }
+
+    /// Same as [`Self::new`], but every level places its vertices with the given rule.
+    pub fn new_with(
+        min_binning_depth: usize,
+        level_count: usize,
+        binning_depth_step: usize,
+        midpoint_rule: MidpointRule,
+    ) -> Self {
+        Self::try_new_with(
+            min_binning_depth,
+            level_count,
+            binning_depth_step,
+            midpoint_rule,
+        )
+        .unwrap_or_else(|error| panic!("{error}"))
+    }
+
+    /// Same as [`Self::new`], but returns an error instead of panicking if there are no levels, the
+    /// step is zero, or the highest level would be deeper than [`MAX_BINNING_DEPTH`].
+    pub fn try_new(
+        min_binning_depth: usize,
+        level_count: usize,
+        binning_depth_step: usize,
+    ) -> Result<Self, IcosphereError> {
+        Self::try_new_with(
+            min_binning_depth,
+            level_count,
+            binning_depth_step,
+            MidpointRule::Normalized,
+        )
+    }
+
+    /// Same as [`Self::new_with`], but returns an error instead of panicking. See [`Self::try_new`].
+    pub fn try_new_with(
+        min_binning_depth: usize,
+        level_count: usize,
+        binning_depth_step: usize,
+        midpoint_rule: MidpointRule,
+    ) -> Result<Self, IcosphereError> {
+        if level_count == 0 {
+            return Err(IcosphereError::ZeroLevelCount);
+        }
+
+        if binning_depth_step == 0 {
+            return Err(IcosphereError::ZeroBinningDepthStep);
+        }
+
+        let max_binning_depth = (level_count - 1)
+            .checked_mul(binning_depth_step)
+            .and_then(|depth| depth.checked_add(min_binning_depth))
+            .filter(|&depth| depth <= MAX_BINNING_DEPTH)
+            .ok_or(IcosphereError::BinningDepthTooLarge {
+                binning_depth: min_binning_depth
+                    .saturating_add((level_count - 1).saturating_mul(binning_depth_step)),
+                max_binning_depth: MAX_BINNING_DEPTH,
+            })?;
         let mut levels = Vec::with_capacity(max_binning_depth - min_binning_depth + 1);
 
-        for binning_depth in min_binning_depth..=max_binning_depth {
-            levels.push(S::create(binning_depth));
+        let mut base = S::create_filled(0);
+        base.set_midpoint_rule(midpoint_rule);
+
+        for _ in 0..min_binning_depth {
+            base = base.subdivide();
+        }
+
+        levels.push(base);
+
+        for binning_depth in (min_binning_depth + 1)..=max_binning_depth {
+            let mut ico = S::create(binning_depth);
+            ico.set_midpoint_rule(midpoint_rule);
+
+            levels.push(ico);
         }
 
-        Self {
+        Ok(Self {
             levels,
             min_binning_depth,
             max_binning_depth,
             binning_depth_step,
             _phantom: PhantomData,
-        }
+        })
     }
 
     fn index_at_level(&self, level: usize) -> usize {
         level * self.binning_depth_step
     }
 
-    /// Ensures the specified chunk is generated. Returns `true` if it was generated, and `false`
-    /// if it has already been generated.
+    /// Ensures the specified chunk is generated, along with the triangles containing it at every lower
+    /// binning depth, which are needed to subdivide it. Returns `true` if anything was generated, and
+    /// `false` if it has already been generated.
+    ///
+    /// Afterwards, every triangle of [`Self::chunk_triangles`] is present in [`Self::get`]`(level)`.
+    /// If `binning_depth_step > 1`, the binning depths between this level and the one below are
+    /// generated for the chunk too, since each is subdivided from the previous one.
+    ///
+    /// The 0th level is always generated, so this always returns `false` for it.
+    ///
+    /// Panics if the level or the chunk index is out of range. See [`Self::try_update_chunk`].
     pub fn update_chunk(&mut self, level: usize, chunk_index: usize) -> bool {
-        let index = self.index_at_level(level);
-        let (previous_levels, next_levels) = self.levels.split_at_mut(index);
+        if level == 0 {
+            return false;
+        }
+
+        // The chunk is made of the descendants of one triangle, `binning_depth_step` depths up
+        let parent_depth_index = self.index_at_level(level) - self.binning_depth_step;
+        let mut generated = self.update_ancestors(parent_depth_index, chunk_index);
+
+        for generation in 0..self.binning_depth_step {
+            let (previous_levels, next_levels) = self
+                .levels
+                .split_at_mut(parent_depth_index + generation + 1);
+
+            let previous = previous_levels.last().unwrap();
+            let current = next_levels.first_mut().unwrap();
+
+            for triangle_index in
+                (chunk_index << (2 * generation))..((chunk_index + 1) << (2 * generation))
+            {
+                generated |= current.subdivide_chunk(previous, triangle_index);
+            }
+        }
+
+        generated
+    }
+
+    /// Ensures every given chunk at `level` is generated, along with the triangles containing them at
+    /// every lower binning depth, like [`Self::update_chunk`]. Returns `true` if anything was
+    /// generated.
+    ///
+    /// Unlike calling [`Self::update_chunk`] for each chunk, every binning depth is generated for all
+    /// of the chunks at once with [`Icosphere::par_subdivide_chunks`], so the vertices are created in
+    /// parallel. Their order is the same every time for the same chunks, but differs from updating
+    /// the chunks one at a time.
+    ///
+    /// Panics if the level or any chunk index is out of range.
+    #[cfg(feature = "rayon")]
+    pub fn par_update_chunks(&mut self, level: usize, chunk_indices: &[usize]) -> bool
+    where
+        T: Send + Sync,
+    {
+        if level == 0 {
+            return false;
+        }
+
+        let target_index = self.index_at_level(level);
+        let parent_depth_index = target_index - self.binning_depth_step;
+        let mut generated = false;
+
+        for index in 1..=target_index {
+            let parent_indices: Vec<usize> = if index <= parent_depth_index {
+                // The ancestors of the chunks, each once
+                let mut seen = std::collections::HashSet::new();
+
+                chunk_indices
+                    .iter()
+                    .map(|&chunk_index| {
+                        locate::ancestor_index(chunk_index, parent_depth_index - index + 1)
+                    })
+                    .filter(|&parent_index| seen.insert(parent_index))
+                    .collect()
+            } else {
+                // The chunks themselves, split one binning depth further every time
+                let generation = index - parent_depth_index - 1;
+
+                chunk_indices
+                    .iter()
+                    .flat_map(|&chunk_index| {
+                        (chunk_index << (2 * generation))..((chunk_index + 1) << (2 * generation))
+                    })
+                    .collect()
+            };
+
+            let (previous_levels, next_levels) = self.levels.split_at_mut(index);
+
+            let previous = previous_levels.last().unwrap();
+            let current = next_levels.first_mut().unwrap();
+
+            generated |= current.par_subdivide_chunks(previous, &parent_indices);
+        }
+
+        generated
+    }
+
+    /// Same as [`Self::update_chunk`], but returns an error instead of panicking if the level or the
+    /// chunk index is out of range.
+    pub fn try_update_chunk(
+        &mut self,
+        level: usize,
+        chunk_index: usize,
+    ) -> Result<bool, IcosphereError> {
+        self.check_chunk(level, chunk_index)?;
+
+        Ok(self.update_chunk(level, chunk_index))
+    }
+
+    /// Returns an error if the level or the chunk index is out of range.
+    fn check_chunk(&self, level: usize, chunk_index: usize) -> Result<(), IcosphereError> {
+        if level >= self.level_count() {
+            return Err(IcosphereError::LevelOutOfRange {
+                level,
+                level_count: self.level_count(),
+            });
+        }
 
-        let previous = previous_levels.last().unwrap();
-        let current = next_levels.first_mut().unwrap();
+        if chunk_index >= self.chunk_count(level) {
+            return Err(IcosphereError::ChunkOutOfRange {
+                level,
+                chunk_index,
+                chunk_count: self.chunk_count(level),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Index of the triangle at `level` that contains `direction`. See [`locate::locate_triangle`].
+    ///
+    /// Every chunk on the way down from the 0th level is generated if it isn't already, so the
+    /// returned triangle is always present in [`Self::get`]`(level)`.
+    pub fn locate_triangle(&mut self, level: usize, direction: Vec3) -> usize {
+        let triangle_index = locate::locate_triangle_with(
+            direction,
+            self.binning_depth_at_level(level),
+            self.levels[0].midpoint_rule(),
+        );
+        self.update_path(level, triangle_index);
+
+        triangle_index
+    }
+
+    /// Casts a ray against the icosphere at `level`. See [`raycast::raycast`].
+    ///
+    /// If a triangle is hit, every chunk on the way down to it from the 0th level is generated if
+    /// it isn't already.
+    pub fn raycast(&mut self, level: usize, origin: Vec3, direction: Vec3) -> Option<RayHit> {
+        let hit = raycast::raycast_with(
+            origin,
+            direction,
+            self.binning_depth_at_level(level),
+            self.levels[0].midpoint_rule(),
+        )?;
+        self.update_path(level, hit.triangle_index);
+
+        Some(hit)
+    }
+
+    /// Ensures the triangle at `level` and all of its ancestors are generated, one binning depth at a time.
+    fn update_path(&mut self, level: usize, triangle_index: usize) {
+        self.update_ancestors(self.index_at_level(level), triangle_index);
+    }
+
+    /// Same as [`Self::update_path`], but with an index into [`Self::levels`] rather than a level.
+    /// Returns `true` if anything was generated.
+    fn update_ancestors(&mut self, target_index: usize, triangle_index: usize) -> bool {
+        let mut generated = false;
 
-        current.subdivide_chunk(previous, chunk_index)
+        for index in 1..=target_index {
+            let (previous_levels, next_levels) = self.levels.split_at_mut(index);
+
+            let previous = previous_levels.last().unwrap();
+            let current = next_levels.first_mut().unwrap();
+
+            let parent_index = locate::ancestor_index(triangle_index, target_index - index + 1);
+            generated |= current.subdivide_chunk(previous, parent_index);
+        }
+
+        generated
+    }
+
+    /// Binning depth of the triangles that cover the chunks of a level. See [`Self::chunk_cover`].
+    pub(crate) fn cover_binning_depth(&self, level: usize) -> usize {
+        self.binning_depth_at_level(level.saturating_sub(1))
+    }
+
+    /// Corners of the triangle that covers the same area as a chunk. At the 0th level, this is the
+    /// chunk's only triangle, and at every other level it's the triangle one level down with the same
+    /// index as the chunk. It must be generated.
+    pub(crate) fn chunk_cover(&self, level: usize, chunk_index: usize) -> [Vec3; 3] {
+        let index = self
+            .index_at_level(level)
+            .saturating_sub(self.binning_depth_step);
+        let ico = &self.levels[index];
+
+        ico.triangle(chunk_index)
+            .map(|i| ico.vertices()[i as usize].position())
     }
 
     /// Get the icosahedron at the specified level
@@ -87,17 +362,28 @@ where
 
     /// Flattens the triangle indices into a contiguous array. Should be used for things like
     /// index buffers over this chunk.
+    ///
+    /// Contains the three vertex indices of every triangle of [`Self::chunk_triangles`], in order, so
+    /// `3 * chunk_size()` indices at every level but the 0th, where chunks are single triangles. The
+    /// chunk must be generated (see [`Self::update_chunk`]), otherwise this panics.
     pub fn flattened_chunk_indices(&self, level: usize, chunk_index: usize) -> Vec<u32> {
-        let mut indices = vec![0u32; 3 * self.chunk_size()]; // Multiplied by 3 for vertex count
-        let subchunk_indices = self.subchunk_indices(chunk_index);
+        let ico = self.get(level);
 
-        for triangle_index in subchunk_indices {
-            let ico = self.get(level);
-            indices[(triangle_index * 3)..(triangle_index * 3 + 3)]
-                .copy_from_slice(&ico.triangle(triangle_index));
-        }
+        self.chunk_triangles(level, chunk_index)
+            .flat_map(|triangle_index| ico.triangle(triangle_index))
+            .collect()
+    }
 
-        indices
+    /// The range of triangle indices at `level` that make up the chunk at `chunk_index`.
+    ///
+    /// Chunks at the 0th level are single triangles. At every other level, they are the triangles
+    /// covered by one triangle of the level below, which has the same index as the chunk.
+    pub fn chunk_triangles(&self, level: usize, chunk_index: usize) -> Range<usize> {
+        if level == 0 {
+            chunk_index..chunk_index + 1
+        } else {
+            self.subchunk_indices(chunk_index)
+        }
     }
 
     /// The binning depth at a specific detail level.
@@ -110,7 +396,7 @@ where
         // Check bounds
         if binning_depth < self.min_binning_depth
             || binning_depth >= self.binning_depth_at_level(self.levels.len())
-            || (binning_depth - self.min_binning_depth) % self.binning_depth_step != 0
+            || !(binning_depth - self.min_binning_depth).is_multiple_of(self.binning_depth_step)
         {
             return None;
         }
@@ -135,19 +421,20 @@ where
         1 << (2 * self.binning_depth_step)
     }
 
-    /// The number of chunks in a certain level. Chunks are indexed by `0..self.chunk_size()`. See [`Self::chunk_size()`].
+    /// The number of chunks in a certain level. Chunks are indexed by `0..self.chunk_count(level)`. See
+    /// [`Self::chunk_size()`].
     ///
     /// If using dynamic detail level, when rendering a chunk index for level `n`, the sub-chunks inside this chunk
     /// (or in other words, the range of child chunk indices that this chunk index covers for level `n + 1`) are
     /// `(chunk_index * chunk_size)..(chunk_index * chunk_size + chunk_size)`.
     ///
-    /// If level == 0, and it corresponds to a binning depth of zero, then the triangle count of the regular
-    /// icosahedron is returned (20). That is to say, the regular icosahedron is not chunked at all, because
-    /// there is no logical way to split its rendering into chunks (and it's a very simple mesh that should anyway
-    /// be drawn all at once).
+    /// The 0th level is not chunked, because there is no level below it to group its triangles by, so
+    /// every triangle of it is its own chunk, and its triangle count is returned. Its chunk at
+    /// `chunk_index` covers the same area as the chunk at `chunk_index` in level 1. At every other
+    /// level, this is the triangle count of the level below.
     pub fn chunk_count(&self, level: usize) -> usize {
-        if self.binning_depth_at_level(level) == 0 {
-            triangle_count(0)
+        if level == 0 {
+            triangle_count(self.min_binning_depth)
         } else {
             let binning_depth = self.binning_depth_at_level(level - 1);
             triangle_count(binning_depth)
@@ -165,8 +452,112 @@ where
         start..end
     }
 
+    /// The chunks at the next level that cover the same area as the given chunk.
+    pub(crate) fn subchunks(&self, level: usize, chunk_index: usize) -> Vec<(usize, usize)> {
+        // The single triangle of a chunk at the 0th level covers the chunk with the same index in
+        // level 1
+        let subchunks = if level == 0 {
+            chunk_index..chunk_index + 1
+        } else {
+            self.subchunk_indices(chunk_index)
+        };
+
+        subchunks
+            .map(|subchunk_index| (level + 1, subchunk_index))
+            .collect()
+    }
+
     /// Returns a range of all chunk indices for the given level.
     pub fn chunk_indices(&self, level: usize) -> Range<usize> {
         0..self.chunk_count(level)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere};
+
+    type Levels = IcosphereLevels<Vec3, SparseIcosphere<Vec3>>;
+
+    #[test]
+    fn chunk_count_per_level() {
+        let levels = Levels::new(1, 3, 2);
+
+        assert_eq!(levels.chunk_count(0), triangle_count(1));
+        assert_eq!(levels.chunk_count(1), triangle_count(1));
+        assert_eq!(levels.chunk_count(2), triangle_count(3));
+
+        // Every chunk of a level covers `chunk_size` triangles of it
+        for level in 1..3 {
+            assert_eq!(
+                levels.chunk_count(level) * levels.chunk_size(),
+                triangle_count(levels.binning_depth_at_level(level))
+            );
+        }
+    }
+
+    #[test]
+    fn update_chunk_generates_ancestors_and_intermediate_depths() {
+        let mut levels = Levels::new(1, 3, 2);
+        let chunk_index = 37;
+
+        assert!(!levels.update_chunk(0, 5));
+        assert!(levels.update_chunk(2, chunk_index));
+        assert!(!levels.update_chunk(2, chunk_index));
+
+        let ico = levels.get(2);
+        for triangle_index in levels.chunk_triangles(2, chunk_index) {
+            assert!(ico.contains_triangle(triangle_index));
+        }
+        assert_eq!(ico.allocated_triangle_count(), levels.chunk_size());
+
+        // The triangle of level 1 containing it, with its siblings, and its children at the depth
+        // between the two levels
+        assert_eq!(
+            levels.get(1).allocated_triangle_indices(),
+            vec![36, 37, 38, 39]
+        );
+        assert_eq!(
+            levels.levels[3].allocated_triangle_indices(),
+            vec![148, 149, 150, 151]
+        );
+
+        // Only the part of the chunk of level 1 on the way was generated
+        let parent_chunk = locate::ancestor_index(chunk_index, 2);
+        assert!(levels.update_chunk(1, parent_chunk));
+        assert_eq!(
+            levels.get(1).allocated_triangle_indices(),
+            levels.chunk_triangles(1, parent_chunk).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn flattened_chunk_indices_match_static_icosphere() {
+        let mut levels = Levels::new(1, 3, 2);
+
+        for (level, chunk_index) in [(0, 7), (1, 7), (2, 0), (2, 319)] {
+            levels.update_chunk(level, chunk_index);
+
+            let indices = levels.flattened_chunk_indices(level, chunk_index);
+            let expected_len = if level == 0 {
+                3
+            } else {
+                3 * levels.chunk_size()
+            };
+            assert_eq!(indices.len(), expected_len);
+
+            // Vertex indices of a sparse icosphere depend on the generation order, but positions don't
+            let ico = levels.get(level);
+            let reference = StaticIcosphere::<Vec3>::nth(levels.binning_depth_at_level(level));
+            let expected = levels
+                .chunk_triangles(level, chunk_index)
+                .flat_map(|triangle_index| reference.triangles[triangle_index])
+                .map(|vertex_index| reference.vertices[vertex_index as usize]);
+
+            for (vertex_index, expected) in indices.into_iter().zip(expected) {
+                assert!(ico.vertices()[vertex_index as usize].distance(expected) < 1e-6);
+            }
+        }
+    }
+}
@@ -2,7 +2,37 @@ use std::collections::{HashMap, HashSet};
 
 use glam::Vec3;
 
+use crate::{error::IcosphereError, midpoint::MidpointRule};
+
+pub mod adjacency;
+pub mod attributes;
+pub mod cache;
+pub mod canonical;
+pub mod culling;
+pub mod displacement;
+pub mod dual;
+pub mod error;
+pub mod export;
+pub mod geodesic;
 pub mod levels;
+pub mod locate;
+pub mod lod;
+pub mod midpoint;
+pub mod nearest;
+#[cfg(feature = "noise")]
+pub mod noise;
+#[cfg(feature = "rayon")]
+pub mod parallel;
+pub mod pathfinding;
+pub mod raycast;
+pub mod region;
+pub mod statistics;
+pub mod stitch;
+pub mod triangle_id;
+pub mod validation;
+
+/// The largest binning depth whose triangle count fits in a `usize`.
+pub const MAX_BINNING_DEPTH: usize = (usize::BITS as usize - 5) / 2;
 
 /// Vertex count of an icosphere at the given depth.
 pub fn vertex_count(binning_depth: usize) -> usize {
@@ -19,10 +49,98 @@ pub fn approximate_triangle_surface_area(binning_depth: usize, radius: f32) -> f
     (4.0 * std::f32::consts::PI * radius * radius) / triangle_count(binning_depth) as f32
 }
 
+/// Triangles of the regular icosahedron, wound counter-clockwise when viewed from outside.
+pub(crate) const ICOSAHEDRON_TRIANGLES: [[u32; 3]; 20] = [
+    [0, 11, 5],
+    [0, 5, 1],
+    [0, 1, 7],
+    [0, 7, 10],
+    [0, 10, 11],
+    [1, 5, 9],
+    [5, 11, 4],
+    [11, 10, 2],
+    [10, 7, 6],
+    [7, 1, 8],
+    [3, 9, 4],
+    [3, 4, 2],
+    [3, 2, 6],
+    [3, 6, 8],
+    [3, 8, 9],
+    [4, 9, 5],
+    [2, 4, 11],
+    [6, 2, 10],
+    [8, 6, 7],
+    [9, 8, 1],
+];
+
+/// Unit-length vertex positions of the regular icosahedron, indexed by [`ICOSAHEDRON_TRIANGLES`].
+pub(crate) fn icosahedron_positions() -> [Vec3; 12] {
+    let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+
+    [
+        (Vec3::NEG_X + Vec3::Y * t).normalize(),
+        (Vec3::X + Vec3::Y * t).normalize(),
+        (Vec3::NEG_X + Vec3::NEG_Y * t).normalize(),
+        (Vec3::X + Vec3::NEG_Y * t).normalize(),
+        (Vec3::NEG_Y + Vec3::Z * t).normalize(),
+        (Vec3::Y + Vec3::Z * t).normalize(),
+        (Vec3::NEG_Y + Vec3::NEG_Z * t).normalize(),
+        (Vec3::Y + Vec3::NEG_Z * t).normalize(),
+        (Vec3::NEG_Z + Vec3::X * t).normalize(),
+        (Vec3::Z + Vec3::X * t).normalize(),
+        (Vec3::NEG_Z + Vec3::NEG_X * t).normalize(),
+        (Vec3::Z + Vec3::NEG_X * t).normalize(),
+    ]
+}
+
+/// Builds the neighbor sets of every vertex from the edges of the given triangles.
+pub(crate) fn neighbors_from_triangles(triangles: &[[u32; 3]]) -> HashMap<usize, HashSet<usize>> {
+    let mut neighbors: HashMap<usize, HashSet<usize>> = HashMap::new();
+
+    for &[a, b, c] in triangles {
+        let [a, b, c] = [a as usize, b as usize, c as usize];
+
+        for (i, j) in [(a, b), (b, c), (c, a)] {
+            neighbors.entry(i).or_default().insert(j);
+            neighbors.entry(j).or_default().insert(i);
+        }
+    }
+
+    neighbors
+}
+
+/// See [`Icosphere::parent_positions`]. The keys of `midpoints` are the sorted vertex indices of the
+/// edges that were split, and the values are the vertex indices of their midpoints.
+fn parent_positions<T: IcosphereVertex>(
+    vertices: &[T],
+    midpoints: &HashMap<(usize, usize), usize>,
+) -> Vec<Vec3> {
+    let mut positions: Vec<Vec3> = vertices.iter().map(|v| v.surface_position()).collect();
+
+    for (&(i, j), &midpoint) in midpoints {
+        positions[midpoint] =
+            (vertices[i].surface_position() + vertices[j].surface_position()) / 2.0;
+    }
+
+    positions
+}
+
 /// The underlying storage for each icosphere vertex.
 pub trait IcosphereVertex: Clone {
     fn position(&self) -> Vec3;
     fn from_position(position: Vec3, binning_depth: usize) -> Self;
+
+    /// Where the vertex is drawn. Subdivisions and lookups work on the unit sphere, so vertices that
+    /// displace the surface (like [`displacement::DisplacedVertex`]) keep [`Self::position`] there and
+    /// return the displaced position here. Used by the exporters and render meshes.
+    fn surface_position(&self) -> Vec3 {
+        self.position()
+    }
+
+    /// Normal of the surface at [`Self::surface_position`].
+    fn surface_normal(&self) -> Vec3 {
+        self.position().normalize()
+    }
 }
 
 impl IcosphereVertex for Vec3 {
@@ -35,19 +153,98 @@ impl IcosphereVertex for Vec3 {
     }
 }
 
+/// An icosphere that is either fully generated or generated on-the-fly. Implemented by
+/// [`StaticIcosphere`] and [`SparseIcosphere`].
+///
+/// Compared to 0.2.0, implementors must also provide [`Self::neighbors`], which is a breaking change.
+/// Every other method added since has a default, see their docs for what it assumes.
 pub trait Icosphere<T: IcosphereVertex> {
     /// A constructor. If the icosphere is sparse, this may create an empty one
     fn create(binning_depth: usize) -> Self;
 
+    /// A constructor that always generates every vertex and triangle, even if the icosphere is sparse.
+    ///
+    /// The default calls [`Self::create`], which is only correct for icospheres that are never
+    /// sparse, so sparse implementors must override it.
+    fn create_filled(binning_depth: usize) -> Self
+    where
+        Self: Sized,
+    {
+        Self::create(binning_depth)
+    }
+
     /// The number of subdivisions from the regular icosahedron.
     fn binning_depth(&self) -> usize;
 
-    /// Triangle at the given index.
+    /// How vertices are placed when this icosphere is subdivided.
+    ///
+    /// The default is [`MidpointRule::Normalized`], for icospheres that don't support other rules.
+    fn midpoint_rule(&self) -> MidpointRule {
+        MidpointRule::Normalized
+    }
+
+    /// Changes how vertices are placed by future subdivisions. Existing vertices aren't moved.
+    ///
+    /// The default ignores the rule, for icospheres that don't support other rules than the one
+    /// returned by [`Self::midpoint_rule`].
+    fn set_midpoint_rule(&mut self, midpoint_rule: MidpointRule) {
+        let _ = midpoint_rule;
+    }
+
+    /// Triangle at the given index. Panics if it's out of range or isn't generated. See
+    /// [`Self::try_triangle`].
     fn triangle(&self, triangle_index: usize) -> [u32; 3];
 
+    /// Same as [`Self::triangle`], but returns an error instead of panicking if the index is out of
+    /// range or the triangle isn't generated.
+    fn try_triangle(&self, triangle_index: usize) -> Result<[u32; 3], IcosphereError> {
+        if triangle_index >= self.total_triangle_count() {
+            return Err(IcosphereError::TriangleOutOfRange {
+                triangle_index,
+                triangle_count: self.total_triangle_count(),
+            });
+        }
+
+        if !self.contains_triangle(triangle_index) {
+            return Err(IcosphereError::TriangleNotGenerated { triangle_index });
+        }
+
+        Ok(self.triangle(triangle_index))
+    }
+
+    /// Whether the triangle at the given index is generated. If this icosphere is not sparse, this is
+    /// true for every index below the total triangle count.
+    fn contains_triangle(&self, triangle_index: usize) -> bool {
+        triangle_index < self.total_triangle_count()
+    }
+
+    /// Indices of every generated triangle, in ascending order. If this icosphere is not sparse, this is
+    /// every index below the total triangle count.
+    fn allocated_triangle_indices(&self) -> Vec<usize> {
+        (0..self.total_triangle_count()).collect()
+    }
+
     /// List of vertices.
     fn vertices(&self) -> &[T];
 
+    /// For each generated vertex, the indices of the vertices it shares an edge with.
+    fn neighbors(&self) -> &HashMap<usize, HashSet<usize>>;
+
+    /// For each vertex, the position it has on the previous binning depth, for morphing between the
+    /// two, in the same space as [`IcosphereVertex::surface_position`]. Vertices created by
+    /// subdividing are at the middle of the edge they split, before being projected onto the sphere
+    /// or displaced, and every other vertex is where it already was. With
+    /// [`levels::IcosphereLevels`] skipping binning depths, this is not the previous level.
+    ///
+    /// The default returns the current position of every vertex, for icospheres that don't track
+    /// which edge each vertex split, so morphing between binning depths has no effect.
+    fn parent_positions(&self) -> Vec<Vec3> {
+        self.vertices()
+            .iter()
+            .map(|vertex| vertex.surface_position())
+            .collect()
+    }
+
     /// The total possible triangle count in an icosphere with the current binning depth.
     fn total_triangle_count(&self) -> usize;
 
@@ -69,12 +266,113 @@ pub trait Icosphere<T: IcosphereVertex> {
         approximate_triangle_surface_area(self.binning_depth(), radius)
     }
 
+    /// Index of the triangle that contains `direction`, found by descending from the regular icosahedron.
+    /// See [`locate::locate_triangle`].
+    ///
+    /// If this icosphere is sparse, the triangle at the returned index may not be generated yet.
+    fn locate_triangle(&self, direction: Vec3) -> usize {
+        locate::locate_triangle_with(direction, self.binning_depth(), self.midpoint_rule())
+    }
+
+    /// Casts a ray against this icosphere. See [`raycast::raycast`].
+    ///
+    /// If this icosphere is sparse, only generated triangles can be hit: when the ray enters through a
+    /// triangle that isn't generated, the hit is where it leaves through the inner side of a generated
+    /// one, if any. See [`raycast::raycast_all_with`].
+    fn raycast(&self, origin: Vec3, direction: Vec3) -> Option<raycast::RayHit> {
+        raycast::raycast_all_with(
+            origin,
+            direction,
+            self.binning_depth(),
+            self.midpoint_rule(),
+        )
+        .into_iter()
+        .find(|hit| self.contains_triangle(hit.triangle_index))
+    }
+
+    /// Index of the vertex closest to `direction` and the angle to it, in radians. See
+    /// [`nearest::nearest_vertex`].
+    fn nearest_vertex(&self, direction: Vec3) -> Option<(usize, f32)> {
+        nearest::nearest_vertex(self, direction)
+    }
+
+    /// Indices of the `k` vertices closest to `direction` and the angles to them, in radians, from
+    /// closest to farthest. See [`nearest::k_nearest_vertices`].
+    fn k_nearest_vertices(&self, direction: Vec3, k: usize) -> Vec<(usize, f32)> {
+        nearest::k_nearest_vertices(self, direction, k)
+    }
+
+    /// Indices of the three triangles sharing an edge with the given triangle. See
+    /// [`adjacency::edge_neighbors`].
+    ///
+    /// If this icosphere is sparse, the neighbors may not be generated yet.
+    fn triangle_neighbors(&self, triangle_index: usize) -> [usize; 3] {
+        adjacency::edge_neighbors(triangle_index, self.binning_depth())
+    }
+
+    /// Indices of the triangles sharing only a vertex with the given triangle. See
+    /// [`adjacency::vertex_neighbors`].
+    ///
+    /// If this icosphere is sparse, the neighbors may not be generated yet.
+    fn triangle_vertex_neighbors(&self, triangle_index: usize) -> Vec<usize> {
+        adjacency::vertex_neighbors(triangle_index, self.binning_depth())
+    }
+
+    /// Checks the invariants of this icosphere's mesh. See [`validation::validate`].
+    fn validate(&self) -> validation::ValidationReport {
+        validation::validate(self)
+    }
+
+    /// The canonical ID of every vertex, which doesn't depend on the order triangles were generated
+    /// in. See [`canonical::CanonicalVertices`].
+    fn canonical_vertices(&self) -> canonical::CanonicalVertices {
+        canonical::CanonicalVertices::new(self)
+    }
+
     /// Subdivides `previous_triangles[parent_index]` into four children starting at `current_triangles[parent_index * 4]`.
     /// The previous binning depth must be 1 less than the current binning depth.
     ///
-    /// Returns false if nothing was generated, true otherwise.
+    /// Returns false if nothing was generated, true otherwise. Panics if the binning depths aren't
+    /// adjacent or the parent triangle isn't generated. See [`Self::try_subdivide_chunk`].
     fn subdivide_chunk(&mut self, previous: &Self, parent_index: usize) -> bool;
 
+    /// Same as [`Self::subdivide_chunk`], but returns an error instead of panicking if the binning
+    /// depths aren't adjacent, or if the parent triangle is out of range or isn't generated.
+    fn try_subdivide_chunk(
+        &mut self,
+        previous: &Self,
+        parent_index: usize,
+    ) -> Result<bool, IcosphereError> {
+        if previous.binning_depth() + 1 != self.binning_depth() {
+            return Err(IcosphereError::NonAdjacentDepths {
+                previous: previous.binning_depth(),
+                current: self.binning_depth(),
+            });
+        }
+
+        previous.try_triangle(parent_index)?;
+
+        Ok(self.subdivide_chunk(previous, parent_index))
+    }
+
+    /// Same as calling [`Self::subdivide_chunk`] for every parent index in order, but the new vertices
+    /// may be created in parallel. Returns true if anything was generated.
+    ///
+    /// A [`SparseIcosphere`] produces exactly what the serial calls would, creating its midpoint
+    /// vertices on the rayon thread pool, which pays off when [`IcosphereVertex::from_position`] is
+    /// expensive. The default implementation calls [`Self::subdivide_chunk`] for every parent index.
+    #[cfg(feature = "rayon")]
+    fn par_subdivide_chunks(&mut self, previous: &Self, parent_indices: &[usize]) -> bool
+    where
+        T: Send + Sync,
+    {
+        parent_indices
+            .iter()
+            .fold(false, |generated, &parent_index| {
+                self.subdivide_chunk(previous, parent_index) | generated
+            })
+    }
+
     /// Subdivide the entire icosphere.
     fn subdivide(&self) -> Self;
 }
@@ -86,14 +384,16 @@ pub trait Icosphere<T: IcosphereVertex> {
 /// amounts of memory at high subdivisions. If you want to construct the triangles and vertices on-the-fly
 /// to save memory, use a [`SparseIcosphere`].
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct StaticIcosphere<T: IcosphereVertex> {
-    /// The vertices may be in any order, don't rely on the order of this list.
+    /// The vertices may be in any order, don't rely on the order of this list. To correlate vertices
+    /// with other icospheres, use [`Icosphere::canonical_vertices`] or [`Self::canonicalize`].
     pub vertices: Vec<T>,
 
     /// Starting from the triangles of the regular icosahedron, each subdivision splits these triangles
     /// into groups of four. These four triangles are always contiguous and are indexed by the index
     /// of the parent icosahedron. For example, given the index `parent_triangle`, the four child
-    /// triangles are located at `parent_triangle..parent_triangle + 4`.
+    /// triangles are located at `parent_triangle * 4..parent_triangle * 4 + 4`.
     ///
     /// When rendering, if chunks are necessary, treat these groups of triangles as chunks.
     pub triangles: Vec<[u32; 3]>,
@@ -104,6 +404,9 @@ pub struct StaticIcosphere<T: IcosphereVertex> {
     /// Number of subdivisions from the regular icosahedron
     pub binning_depth: usize,
 
+    /// How vertices were placed when subdividing, and how they will be placed by [`Icosphere::subdivide`].
+    pub midpoint_rule: MidpointRule,
+
     /// Meaningless for the regular icosahedron.
     ///
     /// Used to avoid duplicating midpoints.
@@ -113,83 +416,34 @@ pub struct StaticIcosphere<T: IcosphereVertex> {
 impl<T: IcosphereVertex> StaticIcosphere<T> {
     /// The regular icosahedron of binning depth 0.
     pub fn regular() -> Self {
-        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
-
-        let positions = [
-            (Vec3::NEG_X + Vec3::Y * t).normalize(),
-            (Vec3::X + Vec3::Y * t).normalize(),
-            (Vec3::NEG_X + Vec3::NEG_Y * t).normalize(),
-            (Vec3::X + Vec3::NEG_Y * t).normalize(),
-            (Vec3::NEG_Y + Vec3::Z * t).normalize(),
-            (Vec3::Y + Vec3::Z * t).normalize(),
-            (Vec3::NEG_Y + Vec3::NEG_Z * t).normalize(),
-            (Vec3::Y + Vec3::NEG_Z * t).normalize(),
-            (Vec3::NEG_Z + Vec3::X * t).normalize(),
-            (Vec3::Z + Vec3::X * t).normalize(),
-            (Vec3::NEG_Z + Vec3::NEG_X * t).normalize(),
-            (Vec3::Z + Vec3::NEG_X * t).normalize(),
-        ];
-
-        let triangles = vec![
-            [0, 11, 5],
-            [0, 5, 1],
-            [0, 1, 7],
-            [0, 7, 10],
-            [0, 10, 11],
-            [1, 5, 9],
-            [5, 11, 4],
-            [11, 10, 2],
-            [10, 7, 6],
-            [7, 1, 8],
-            [3, 9, 4],
-            [3, 4, 2],
-            [3, 2, 6],
-            [3, 6, 8],
-            [3, 8, 9],
-            [4, 9, 5],
-            [2, 4, 11],
-            [6, 2, 10],
-            [8, 6, 7],
-            [9, 8, 1],
-        ];
-
-        let neighbor_indices = [
-            [11, 5, 1, 7, 10],
-            [5, 9, 7, 8, 9],
-            [11, 10, 3, 4, 6],
-            [9, 4, 2, 6, 8],
-            [5, 11, 3, 9, 2],
-            [0, 11, 1, 9, 4],
-            [10, 7, 3, 2, 8],
-            [0, 1, 10, 6, 8],
-            [3, 6, 7, 9, 1],
-            [1, 5, 3, 8, 4],
-            [0, 7, 11, 2, 6],
-            [0, 5, 10, 4, 2],
-        ];
+        let positions = icosahedron_positions();
+        let triangles = ICOSAHEDRON_TRIANGLES.to_vec();
 
         let vertices: Vec<T> = positions
             .into_iter()
             .map(|p| T::from_position(p, 0))
             .collect();
 
-        let neighbors: HashMap<_, _> = neighbor_indices
-            .into_iter()
-            .map(|n| n.into_iter().collect::<HashSet<usize>>())
-            .enumerate()
-            .collect();
+        let neighbors = neighbors_from_triangles(&triangles);
 
         Self {
             vertices,
             triangles,
             neighbors,
             binning_depth: 0,
+            midpoint_rule: MidpointRule::Normalized,
             midpoints: HashMap::new(),
         }
     }
 
     pub fn nth(binning_depth: usize) -> Self {
+        Self::nth_with(binning_depth, MidpointRule::Normalized)
+    }
+
+    /// Same as [`Self::nth`], but places vertices with the given rule.
+    pub fn nth_with(binning_depth: usize, midpoint_rule: MidpointRule) -> Self {
         let mut ico = Self::regular();
+        ico.midpoint_rule = midpoint_rule;
 
         for _ in 0..binning_depth {
             ico = ico.subdivide();
@@ -197,17 +451,87 @@ impl<T: IcosphereVertex> StaticIcosphere<T> {
 
         ico
     }
+
+    /// Iteratively moves vertices to make the triangle areas more uniform. Each iteration moves every
+    /// vertex to shrink its larger adjacent triangles and grow its smaller ones, by stepping along the
+    /// gradient of each triangle's area. The 12 vertices of the regular icosahedron stay in place.
+    ///
+    /// Unlike the [`MidpointRule`]s, this moves vertices off the positions the hierarchy predicts, so
+    /// [`Icosphere::locate_triangle`] and [`Icosphere::raycast`] may be off by one triangle near edges,
+    /// and further subdivisions won't match [`Self::nth_with`].
+    pub fn relax_areas(&mut self, iterations: usize) {
+        // For each vertex, the other two corners of every triangle around it, in winding order
+        let mut fans = vec![Vec::new(); self.vertices.len()];
+        for (triangle_index, &[a, b, c]) in self.triangles.iter().enumerate() {
+            for (vertex, next, after) in [(a, b, c), (b, c, a), (c, a, b)] {
+                fans[vertex as usize].push((triangle_index, next as usize, after as usize));
+            }
+        }
+
+        let mean_area = 4.0 * std::f64::consts::PI / self.triangles.len() as f64;
+
+        for _ in 0..iterations {
+            let positions: Vec<_> = self
+                .vertices
+                .iter()
+                .map(|v| v.position().as_dvec3().normalize())
+                .collect();
+
+            let areas: Vec<_> = self
+                .triangles
+                .iter()
+                .map(|triangle| {
+                    statistics::spherical_triangle_area(triangle.map(|i| positions[i as usize]))
+                })
+                .collect();
+
+            for (vertex, fan) in fans.iter().enumerate().skip(12) {
+                let position = positions[vertex];
+
+                // Moving a corner perpendicularly away from the opposite edge grows the triangle by
+                // half the edge length per unit moved, so this would fix each triangle on its own
+                let offset = fan
+                    .iter()
+                    .map(|&(triangle_index, next, after)| {
+                        let gradient = (positions[next] - positions[after]).cross(position) / 2.0;
+
+                        gradient * ((mean_area - areas[triangle_index]) / gradient.length_squared())
+                    })
+                    .sum::<glam::DVec3>()
+                    / fan.len() as f64;
+
+                let position = (position + offset * RELAXATION_STEP).normalize();
+                self.vertices[vertex] = T::from_position(position.as_vec3(), self.binning_depth);
+            }
+        }
+    }
 }
 
+/// Fraction of the correction applied in each iteration of [`StaticIcosphere::relax_areas`]. Every
+/// vertex moves at once, so applying all of it overshoots.
+const RELAXATION_STEP: f64 = 0.75;
+
 impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
     fn create(binning_depth: usize) -> Self {
         Self::nth(binning_depth)
     }
 
+    fn create_filled(binning_depth: usize) -> Self {
+        Self::nth(binning_depth)
+    }
+
     fn binning_depth(&self) -> usize {
         self.binning_depth
     }
 
+    fn midpoint_rule(&self) -> MidpointRule {
+        self.midpoint_rule
+    }
+
+    fn set_midpoint_rule(&mut self, midpoint_rule: MidpointRule) {
+        self.midpoint_rule = midpoint_rule;
+    }
+
     fn triangle(&self, triangle_index: usize) -> [u32; 3] {
         self.triangles[triangle_index]
     }
@@ -216,6 +540,14 @@ impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
         &self.vertices
     }
 
+    fn neighbors(&self) -> &HashMap<usize, HashSet<usize>> {
+        &self.neighbors
+    }
+
+    fn parent_positions(&self) -> Vec<Vec3> {
+        parent_positions(&self.vertices, &self.midpoints)
+    }
+
     fn total_triangle_count(&self) -> usize {
         triangle_count(self.binning_depth)
     }
@@ -250,14 +582,15 @@ impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
             for (edge_index, (i, j)) in segments.into_iter().enumerate() {
                 let key = if i > j { (j, i) } else { (i, j) };
 
-                let midpoint_index = match self.midpoints.get(&key) {
+                let midpoint_index = match midpoints.get(&key) {
                     Some(&midpoint_index) => midpoint_index,
                     None => {
-                        let midpoint =
-                            (vertices[i].position() + vertices[j].position()).normalize();
+                        let midpoint = self
+                            .midpoint_rule
+                            .midpoint(vertices[i].position(), vertices[j].position());
 
                         let midpoint_index = vertices.len();
-                        vertices.push(T::from_position(midpoint, self.binning_depth));
+                        vertices.push(T::from_position(midpoint, self.binning_depth + 1));
 
                         // Cache the midpoint so we don't duplicate when processing a different triangle
                         midpoints.insert(key, midpoint_index);
@@ -272,15 +605,21 @@ impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
                 };
 
                 segment_midpoints[edge_index] = midpoint_index as u32;
+            }
 
-                let [a, b, c] = [a as u32, b as u32, c as u32];
-                let [d, e, f] = segment_midpoints;
+            let [a, b, c] = [a as u32, b as u32, c as u32];
+            let [d, e, f] = segment_midpoints;
 
-                triangles.push([a, d, f]);
-                triangles.push([b, e, d]);
-                triangles.push([c, f, e]);
-                triangles.push([d, e, f]);
+            // The midpoints are connected by the edges of the center child
+            for (i, j) in [(d, e), (e, f), (f, d)] {
+                neighbors.entry(i as usize).or_default().insert(j as usize);
+                neighbors.entry(j as usize).or_default().insert(i as usize);
             }
+
+            triangles.push([a, d, f]);
+            triangles.push([b, e, d]);
+            triangles.push([c, f, e]);
+            triangles.push([d, e, f]);
         }
 
         Self {
@@ -288,6 +627,7 @@ impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
             triangles,
             neighbors,
             binning_depth: self.binning_depth + 1,
+            midpoint_rule: self.midpoint_rule,
             midpoints,
         }
     }
@@ -295,13 +635,15 @@ impl<T: IcosphereVertex> Icosphere<T> for StaticIcosphere<T> {
 
 /// A sparse icosphere that generates vertices and triangles on-the-fly, using dramatically less memory at high binning depths.
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SparseIcosphere<T: IcosphereVertex> {
     /// Since vertices are added on-the-fly as needed, don't expect this to be in any particular order.
+    /// To correlate vertices with other icospheres, use [`Icosphere::canonical_vertices`].
     pub vertices: Vec<T>,
 
     /// A sparse vector of triangle indices. Keys are in no particular order for the regular icosahedron,
     /// but are expanded fourfold for subdivisions. For example, for `triangle_index` in
-    /// the regular icosahedron, its four subdivisions are `triangle_index..triangle_index + 4`.
+    /// the regular icosahedron, its four subdivisions are `triangle_index * 4..triangle_index * 4 + 4`.
     pub triangles: HashMap<usize, [u32; 3]>,
 
     /// Sparse vector of the neighbors of each vertex for this icosphere. The keys are vertex indices and
@@ -311,6 +653,9 @@ pub struct SparseIcosphere<T: IcosphereVertex> {
     /// Number of subdivisions from the regular icosahedron
     pub binning_depth: usize,
 
+    /// How vertices are placed by [`Icosphere::subdivide_chunk`].
+    pub midpoint_rule: MidpointRule,
+
     /// Meaningless for the regular icosahedron. The keys are sorted pairs of vertex indices and the
     /// values are the vertex indices of the neighbor between them.
     midpoints: HashMap<(usize, usize), usize>,
@@ -328,6 +673,7 @@ impl<T: IcosphereVertex> SparseIcosphere<T> {
         let triangles = ico.triangles.into_iter().enumerate().collect();
         let neighbors = ico.neighbors;
         let binning_depth = ico.binning_depth;
+        let midpoint_rule = ico.midpoint_rule;
         let midpoints = ico.midpoints;
 
         Self {
@@ -335,6 +681,7 @@ impl<T: IcosphereVertex> SparseIcosphere<T> {
             triangles,
             neighbors,
             binning_depth,
+            midpoint_rule,
             midpoints,
             // The entire icosphere is constructed at once,
             // so there's no need for this cache
@@ -354,6 +701,7 @@ impl<T: IcosphereVertex> SparseIcosphere<T> {
             triangles: HashMap::new(),
             neighbors: HashMap::new(),
             binning_depth,
+            midpoint_rule: MidpointRule::Normalized,
             midpoints: HashMap::new(),
             previous_vertices: HashMap::new(),
         }
@@ -361,7 +709,12 @@ impl<T: IcosphereVertex> SparseIcosphere<T> {
 
     /// Construct a sparse icosphere at the given binning depth that is filled (i.e. completely generated)
     pub fn filled(binning_depth: usize) -> Self {
-        Self::from_static(StaticIcosphere::nth(binning_depth))
+        Self::filled_with(binning_depth, MidpointRule::Normalized)
+    }
+
+    /// Same as [`Self::filled`], but places vertices with the given rule.
+    pub fn filled_with(binning_depth: usize, midpoint_rule: MidpointRule) -> Self {
+        Self::from_static(StaticIcosphere::nth_with(binning_depth, midpoint_rule))
     }
 }
 
@@ -370,18 +723,49 @@ impl<T: IcosphereVertex> Icosphere<T> for SparseIcosphere<T> {
         Self::empty(binning_depth)
     }
 
+    fn create_filled(binning_depth: usize) -> Self {
+        Self::filled(binning_depth)
+    }
+
     fn binning_depth(&self) -> usize {
         self.binning_depth
     }
 
+    fn midpoint_rule(&self) -> MidpointRule {
+        self.midpoint_rule
+    }
+
+    fn set_midpoint_rule(&mut self, midpoint_rule: MidpointRule) {
+        self.midpoint_rule = midpoint_rule;
+    }
+
     fn triangle(&self, triangle_index: usize) -> [u32; 3] {
         self.triangles[&triangle_index]
     }
 
+    fn contains_triangle(&self, triangle_index: usize) -> bool {
+        self.triangles.contains_key(&triangle_index)
+    }
+
+    fn allocated_triangle_indices(&self) -> Vec<usize> {
+        let mut triangle_indices: Vec<usize> = self.triangles.keys().copied().collect();
+        triangle_indices.sort_unstable();
+
+        triangle_indices
+    }
+
     fn vertices(&self) -> &[T] {
         &self.vertices
     }
 
+    fn neighbors(&self) -> &HashMap<usize, HashSet<usize>> {
+        &self.neighbors
+    }
+
+    fn parent_positions(&self) -> Vec<Vec3> {
+        parent_positions(&self.vertices, &self.midpoints)
+    }
+
     fn total_triangle_count(&self) -> usize {
         triangle_count(self.binning_depth)
     }
@@ -399,6 +783,57 @@ impl<T: IcosphereVertex> Icosphere<T> for SparseIcosphere<T> {
     }
 
     fn subdivide_chunk(&mut self, previous: &Self, parent_index: usize) -> bool {
+        self.subdivide_chunk_deferred(previous, parent_index, None)
+    }
+
+    #[cfg(feature = "rayon")]
+    fn par_subdivide_chunks(&mut self, previous: &Self, parent_indices: &[usize]) -> bool
+    where
+        T: Send + Sync,
+    {
+        let mut deferred = Vec::new();
+
+        let generated = parent_indices
+            .iter()
+            .fold(false, |generated, &parent_index| {
+                self.subdivide_chunk_deferred(previous, parent_index, Some(&mut deferred))
+                    | generated
+            });
+
+        parallel::create_midpoints(
+            &mut self.vertices,
+            &deferred,
+            self.midpoint_rule,
+            self.binning_depth,
+        );
+
+        generated
+    }
+
+    /// Requires this icosphere to be completely generated before subdividing
+    fn subdivide(&self) -> Self {
+        let mut ico = Self::empty(self.binning_depth + 1);
+        ico.midpoint_rule = self.midpoint_rule;
+
+        // In order, so the vertices are in the same order every time
+        for chunk_index in self.allocated_triangle_indices() {
+            ico.subdivide_chunk(self, chunk_index);
+        }
+
+        ico
+    }
+}
+
+impl<T: IcosphereVertex> SparseIcosphere<T> {
+    /// See [`Icosphere::subdivide_chunk`]. If `deferred` is given, new midpoint vertices are left as
+    /// copies of one of their endpoints, and `[midpoint, i, j]` is pushed to it for each of them, to
+    /// be created later from the vertices at `i` and `j`.
+    fn subdivide_chunk_deferred(
+        &mut self,
+        previous: &Self,
+        parent_index: usize,
+        mut deferred: Option<&mut Vec<[usize; 3]>>,
+    ) -> bool {
         // can only subdivide adjacent binning depths
         if previous.binning_depth + 1 != self.binning_depth {
             panic!("Attempted to subdivide icospheres with non-adjacent depth");
@@ -448,13 +883,23 @@ impl<T: IcosphereVertex> Icosphere<T> for SparseIcosphere<T> {
             let midpoint_index = match self.midpoints.get(&key) {
                 Some(&midpoint_index) => midpoint_index,
                 None => {
-                    let midpoint = (previous.vertices[i].position()
-                        + previous.vertices[j].position())
-                    .normalize();
-
                     let midpoint_index = self.vertices.len();
-                    self.vertices
-                        .push(T::from_position(midpoint, self.binning_depth));
+
+                    let vertex = match deferred.as_deref_mut() {
+                        Some(deferred) => {
+                            deferred.push([midpoint_index, i, j]);
+                            self.vertices[i].clone()
+                        }
+                        None => {
+                            let midpoint = self
+                                .midpoint_rule
+                                .midpoint(self.vertices[i].position(), self.vertices[j].position());
+
+                            T::from_position(midpoint, self.binning_depth)
+                        }
+                    };
+
+                    self.vertices.push(vertex);
 
                     // Cache the midpoint so we don't duplicate when processing a different triangle
                     self.midpoints.insert(key, midpoint_index);
@@ -474,6 +919,18 @@ impl<T: IcosphereVertex> Icosphere<T> for SparseIcosphere<T> {
         let [a, b, c] = [a as u32, b as u32, c as u32];
         let [d, e, f] = midpoints;
 
+        // The midpoints are connected by the edges of the center child
+        for (i, j) in [(d, e), (e, f), (f, d)] {
+            self.neighbors
+                .entry(i as usize)
+                .or_default()
+                .insert(j as usize);
+            self.neighbors
+                .entry(j as usize)
+                .or_default()
+                .insert(i as usize);
+        }
+
         // Each triangle gets four children, so we multiply the original index by four to have space
         let new_triangle_index = parent_index * 4;
 
@@ -484,15 +941,135 @@ impl<T: IcosphereVertex> Icosphere<T> for SparseIcosphere<T> {
 
         true
     }
+}
 
-    /// Requires this icosphere to be completely generated before subdividing
-    fn subdivide(&self) -> Self {
-        let mut ico = Self::empty(self.binning_depth + 1);
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        for &chunk_index in self.triangles.keys() {
-            ico.subdivide_chunk(self, chunk_index);
+    /// Only implements the required methods of [`Icosphere`].
+    struct Icosahedron {
+        vertices: Vec<Vec3>,
+        neighbors: HashMap<usize, HashSet<usize>>,
+    }
+
+    impl Icosphere<Vec3> for Icosahedron {
+        fn create(_binning_depth: usize) -> Self {
+            Self {
+                vertices: icosahedron_positions().to_vec(),
+                neighbors: neighbors_from_triangles(&ICOSAHEDRON_TRIANGLES),
+            }
         }
 
-        ico
+        fn binning_depth(&self) -> usize {
+            0
+        }
+
+        fn triangle(&self, triangle_index: usize) -> [u32; 3] {
+            ICOSAHEDRON_TRIANGLES[triangle_index]
+        }
+
+        fn vertices(&self) -> &[Vec3] {
+            &self.vertices
+        }
+
+        fn neighbors(&self) -> &HashMap<usize, HashSet<usize>> {
+            &self.neighbors
+        }
+
+        fn total_triangle_count(&self) -> usize {
+            triangle_count(0)
+        }
+
+        fn total_vertex_count(&self) -> usize {
+            vertex_count(0)
+        }
+
+        fn subdivide_chunk(&mut self, _previous: &Self, _parent_index: usize) -> bool {
+            false
+        }
+
+        fn subdivide(&self) -> Self {
+            panic!("the test icosahedron only exists at binning depth 0")
+        }
+    }
+
+    #[test]
+    fn defaults_of_optional_methods() {
+        let mut ico = Icosahedron::create_filled(0);
+
+        assert_eq!(ico.midpoint_rule(), MidpointRule::Normalized);
+        ico.set_midpoint_rule(MidpointRule::Slerp);
+        assert_eq!(ico.midpoint_rule(), MidpointRule::Normalized);
+
+        assert_eq!(ico.parent_positions(), icosahedron_positions());
+        assert_eq!(ico.allocated_triangle_count(), 20);
+        assert!(ico.validate().is_valid());
+    }
+
+    /// Checks that the vertices of `ico` that already were in `previous` keep their position, and
+    /// that every other one starts at the middle of the edge of `previous` it split.
+    fn assert_parent_positions<S: Icosphere<Vec3>>(previous: &S, ico: &S) {
+        let previous_vertices = previous.vertices();
+        let edges: Vec<(Vec3, Vec3)> = previous
+            .neighbors()
+            .iter()
+            .flat_map(|(&i, neighbors)| {
+                neighbors
+                    .iter()
+                    .filter(move |&&j| i < j)
+                    .map(move |&j| (previous_vertices[i], previous_vertices[j]))
+            })
+            .collect();
+
+        let parent_positions = ico.parent_positions();
+        assert_eq!(parent_positions.len(), ico.vertices().len());
+
+        for (vertex, parent_position) in ico.vertices().iter().zip(parent_positions) {
+            if previous_vertices.contains(vertex) {
+                assert_eq!(parent_position, *vertex);
+            } else {
+                let (a, b) = edges
+                    .iter()
+                    .find(|(a, b)| (*a + *b).normalize().distance(*vertex) < 1e-6)
+                    .unwrap();
+
+                assert!(parent_position.distance((*a + *b) / 2.0) < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn parent_positions_of_static_icosphere() {
+        for binning_depth in 1..4 {
+            assert_parent_positions(
+                &StaticIcosphere::<Vec3>::nth(binning_depth - 1),
+                &StaticIcosphere::nth(binning_depth),
+            );
+        }
+    }
+
+    #[test]
+    fn parent_positions_of_sparse_icosphere() {
+        let previous = SparseIcosphere::<Vec3>::filled(1);
+        let mut ico = SparseIcosphere::empty(2);
+
+        for parent_index in [0, 5, 17, 63] {
+            ico.subdivide_chunk(&previous, parent_index);
+        }
+
+        assert_parent_positions(&previous, &ico);
+    }
+
+    #[test]
+    fn neighbors_of_subdivisions() {
+        for binning_depth in 0..4 {
+            let ico = StaticIcosphere::<Vec3>::nth(binning_depth);
+
+            assert_eq!(ico.neighbors.len(), vertex_count(binning_depth));
+            assert_eq!(ico.neighbors.values().filter(|n| n.len() == 5).count(), 12);
+            assert!(ico.neighbors.values().all(|n| n.len() == 5 || n.len() == 6));
+            assert_eq!(ico.neighbors, neighbors_from_triangles(&ico.triangles));
+        }
     }
 }
@@ -0,0 +1,203 @@
+use glam::Vec3;
+
+use crate::{
+    ICOSAHEDRON_TRIANGLES, icosahedron_positions,
+    midpoint::{Face, MidpointRule},
+};
+
+/// The maximum number of triangles stepped through after descending with [`MidpointRule::EqualArea`].
+const MAX_EDGE_STEPS: usize = 4;
+
+/// Finds the index of the triangle containing `direction` in an icosphere of the given binning depth.
+///
+/// This doesn't need an icosphere to exist: starting from the 20 faces of the regular icosahedron,
+/// the containing triangle is narrowed down to one of its four children (at `parent_index * 4`) once
+/// per subdivision, so this runs in O(binning depth). The returned index refers to the same triangle
+/// in a [`crate::StaticIcosphere`] or [`crate::SparseIcosphere`] of that binning depth.
+///
+/// `direction` doesn't need to be normalized, but it must not be zero.
+pub fn locate_triangle(direction: Vec3, binning_depth: usize) -> usize {
+    locate_triangle_with(direction, binning_depth, MidpointRule::Normalized)
+}
+
+/// Same as [`locate_triangle`], for an icosphere whose vertices were placed with the given rule.
+pub fn locate_triangle_with(
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> usize {
+    locate_triangle_corners(direction, binning_depth, midpoint_rule).0
+}
+
+/// Same as [`locate_triangle`], but also returns the positions of the triangle's corners, in the
+/// same order as the triangle's vertex indices.
+pub(crate) fn locate_triangle_corners(
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> (usize, [Vec3; 3]) {
+    let (mut triangle_index, mut corners) = descend(direction, binning_depth, midpoint_rule);
+
+    if midpoint_rule != MidpointRule::EqualArea {
+        return (triangle_index, corners);
+    }
+
+    // The mesh's edges are straight between the vertices, while the lines that split the triangles
+    // on the face map to slight curves on the sphere, so points close to an edge may be in the
+    // triangle across it. That triangle's center is far enough from the edge to be found reliably.
+    for _ in 0..MAX_EDGE_STEPS {
+        let [a, b, c] = corners;
+        let outside = [(a, b, c), (b, c, a), (c, a, b)]
+            .into_iter()
+            .find(|(from, to, _)| {
+                direction
+                    .as_dvec3()
+                    .dot(from.as_dvec3().cross(to.as_dvec3()))
+                    < 0.0
+            });
+
+        let Some((from, to, opposite)) = outside else {
+            break;
+        };
+
+        (triangle_index, corners) =
+            descend((from + to) * 2.0 - opposite, binning_depth, midpoint_rule);
+    }
+
+    (triangle_index, corners)
+}
+
+/// Narrows down the triangle containing `direction` from the regular icosahedron, one subdivision at a
+/// time.
+fn descend(
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> (usize, [Vec3; 3]) {
+    let (mut triangle_index, mut corners) = locate_base_triangle(direction);
+
+    // Equal-area midpoints lie on straight lines across the flat face rather than on great circles,
+    // so the children are told apart on the face instead
+    let flat = (midpoint_rule == MidpointRule::EqualArea).then(|| {
+        let face = Face::new(triangle_index);
+        let point = face.project(direction.as_dvec3().normalize());
+
+        (face, point)
+    });
+
+    let is_left = |from: Vec3, to: Vec3| match &flat {
+        Some((face, point)) => {
+            let (from, to) = (face.project(from.as_dvec3()), face.project(to.as_dvec3()));
+            (to - from).perp_dot(*point - from) >= 0.0
+        }
+        // The cross product of two close directions loses most of its precision in `f32`, enough to
+        // pick the wrong child past binning depth 12 or so
+        None => {
+            let direction = direction.as_dvec3();
+            direction.dot(from.as_dvec3().cross(to.as_dvec3())) >= 0.0
+        }
+    };
+
+    for _ in 0..binning_depth {
+        let (child, child_corners) = locate_child(is_left, corners, midpoint_rule);
+
+        triangle_index = triangle_index * 4 + child;
+        corners = child_corners;
+    }
+
+    (triangle_index, corners)
+}
+
+/// Finds which of the 20 faces of the regular icosahedron contains `direction`, along with the
+/// positions of its corners.
+pub(crate) fn locate_base_triangle(direction: Vec3) -> (usize, [Vec3; 3]) {
+    let positions = icosahedron_positions();
+
+    // All faces of the icosahedron are tangent to the same sphere, so the face hit by a ray from the
+    // origin is the one whose center is closest in direction.
+    let (triangle_index, _) = ICOSAHEDRON_TRIANGLES
+        .iter()
+        .map(|&[a, b, c]| positions[a as usize] + positions[b as usize] + positions[c as usize])
+        .map(|center| center.dot(direction))
+        .enumerate()
+        .max_by(|(_, x), (_, y)| x.total_cmp(y))
+        .unwrap();
+
+    let [a, b, c] = ICOSAHEDRON_TRIANGLES[triangle_index];
+
+    (
+        triangle_index,
+        [
+            positions[a as usize],
+            positions[b as usize],
+            positions[c as usize],
+        ],
+    )
+}
+
+/// Positions of the corners of a triangle, computed by subdividing down from its face of the regular
+/// icosahedron. These are the same positions an icosphere of that binning depth would have.
+pub(crate) fn triangle_corners(
+    triangle_index: usize,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> [Vec3; 3] {
+    let positions = icosahedron_positions();
+    let face = ancestor_index(triangle_index, binning_depth);
+    let mut corners = ICOSAHEDRON_TRIANGLES[face].map(|i| positions[i as usize]);
+
+    for generation in (0..binning_depth).rev() {
+        let child = ancestor_index(triangle_index, generation) % 4;
+        corners = child_corners(corners, child, midpoint_rule);
+    }
+
+    corners
+}
+
+/// Positions of the corners of the child at `parent_index * 4 + child`, given the parent's corners.
+pub(crate) fn child_corners(
+    [a, b, c]: [Vec3; 3],
+    child: usize,
+    midpoint_rule: MidpointRule,
+) -> [Vec3; 3] {
+    let d = midpoint_rule.midpoint(a, b);
+    let e = midpoint_rule.midpoint(b, c);
+    let f = midpoint_rule.midpoint(c, a);
+
+    match child {
+        0 => [a, d, f],
+        1 => [b, e, d],
+        2 => [c, f, e],
+        _ => [d, e, f],
+    }
+}
+
+/// Given the corners of a triangle containing the located point, finds which of its four children
+/// contains it, where `is_left(from, to)` tells whether the point is on the left of the edge from
+/// `from` to `to` when viewed from outside. Returns the child's offset from `parent_index * 4` and the
+/// positions of its corners.
+fn locate_child(
+    is_left: impl Fn(Vec3, Vec3) -> bool,
+    [a, b, c]: [Vec3; 3],
+    midpoint_rule: MidpointRule,
+) -> (usize, [Vec3; 3]) {
+    let d = midpoint_rule.midpoint(a, b);
+    let e = midpoint_rule.midpoint(b, c);
+    let f = midpoint_rule.midpoint(c, a);
+
+    // Each corner child is separated from the center child by its inner edge
+    if is_left(d, f) {
+        (0, [a, d, f])
+    } else if is_left(e, d) {
+        (1, [b, e, d])
+    } else if is_left(f, e) {
+        (2, [c, f, e])
+    } else {
+        (3, [d, e, f])
+    }
+}
+
+/// The index of the ancestor of `triangle_index` that is `generations` subdivisions above it.
+pub fn ancestor_index(triangle_index: usize, generations: usize) -> usize {
+    triangle_index >> (2 * generations)
+}
@@ -0,0 +1,356 @@
+use glam::Vec3;
+
+use crate::{
+    Icosphere, IcosphereVertex,
+    culling::{ChunkBounds, Frustum},
+    levels::IcosphereLevels,
+};
+
+/// What the camera sees, for choosing which chunks of [`IcosphereLevels`] to render. See
+/// [`IcosphereLevels::select_chunks`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LodParameters {
+    /// Position of the camera, in the same space as the sphere, which is centered on the origin.
+    pub camera_position: Vec3,
+
+    /// Vertical field of view, in radians.
+    pub vertical_fov: f32,
+
+    /// Height of the viewport, in pixels.
+    pub screen_height: f32,
+
+    /// The largest distance, in pixels, between a rendered chunk and the true sphere, as projected on
+    /// the screen. Chunks with a larger error are replaced by their more detailed subchunks.
+    pub error_threshold: f32,
+
+    /// Radius the icosphere is rendered at.
+    pub radius: f32,
+
+    /// If set, chunks outside this frustum are neither generated nor selected. It must be in the
+    /// same space as [`Self::camera_position`].
+    pub frustum: Option<Frustum>,
+
+    /// If true, chunks behind the horizon as seen from [`Self::camera_position`] are neither
+    /// generated nor selected. See [`ChunkBounds::is_behind_horizon`].
+    pub horizon_culling: bool,
+
+    /// If set, the lowest and highest heights of a [`crate::displacement::Heightmap`] the icosphere
+    /// is displaced by, and chunks are culled and measured with [`ChunkBounds::displaced`]. Horizon
+    /// culling then uses [`ChunkBounds::is_occluded_by_sphere`], with the largest sphere that is
+    /// below every triangle.
+    pub height_range: Option<(f32, f32)>,
+}
+
+impl LodParameters {
+    pub fn new(
+        camera_position: Vec3,
+        vertical_fov: f32,
+        screen_height: f32,
+        error_threshold: f32,
+        radius: f32,
+    ) -> Self {
+        Self {
+            camera_position,
+            vertical_fov,
+            screen_height,
+            error_threshold,
+            radius,
+            frustum: None,
+            horizon_culling: false,
+            height_range: None,
+        }
+    }
+
+    pub fn with_frustum(mut self, frustum: Frustum) -> Self {
+        self.frustum = Some(frustum);
+        self
+    }
+
+    pub fn with_horizon_culling(mut self) -> Self {
+        self.horizon_culling = true;
+        self
+    }
+
+    pub fn with_height_range(mut self, min_height: f32, max_height: f32) -> Self {
+        self.height_range = Some((min_height, max_height));
+        self
+    }
+
+    /// Bounds of a chunk as rendered, from its bounds on the unit sphere.
+    fn rendered_bounds(&self, bounds: &ChunkBounds) -> ChunkBounds {
+        match self.height_range {
+            Some((min_height, max_height)) => bounds.displaced(self.radius, min_height, max_height),
+            None => bounds.scaled(self.radius),
+        }
+    }
+
+    /// Whether a chunk with the given bounds, on the unit sphere, can't be seen. `max_error` is the
+    /// largest [`ChunkBounds::error`] of any chunk, which places the occluder for displaced chunks.
+    fn is_culled(&self, bounds: &ChunkBounds, max_error: f32) -> bool {
+        let rendered = self.rendered_bounds(bounds);
+
+        let behind_horizon = match self.height_range {
+            // Triangles can't be closer to the center than their vertices by more than their error
+            Some((min_height, _)) => rendered.is_occluded_by_sphere(
+                self.camera_position,
+                (self.radius + min_height) * (1.0 - max_error),
+            ),
+            None => rendered.is_behind_horizon(self.camera_position),
+        };
+
+        self.frustum
+            .is_some_and(|frustum| !rendered.intersects_frustum(&frustum))
+            || (self.horizon_culling && behind_horizon)
+    }
+
+    /// How many pixels a distance of one covers at the given distance from the camera.
+    fn pixels_per_unit(&self, distance: f32) -> f32 {
+        self.screen_height / (2.0 * distance * (self.vertical_fov / 2.0).tan())
+    }
+}
+
+impl<T, S> IcosphereLevels<T, S>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    /// Chooses the chunks to render as `(level, chunk_index)` pairs, such that each is as coarse as
+    /// possible while its error on the screen stays within [`LodParameters::error_threshold`].
+    ///
+    /// Starting from every chunk of the 0th level, chunks whose error is too large are replaced by
+    /// their subchunks (see [`Self::subchunk_indices`]), which are generated with
+    /// [`Self::update_chunk`] if they aren't already. The chunks at the highest level are never
+    /// replaced. Without culling, the selected chunks cover the whole sphere without overlapping, but
+    /// neighboring chunks may be at different levels.
+    ///
+    /// A chunk's error is [`ChunkBounds::error`], projected at the distance between the camera and the
+    /// closest point of the chunk's bounding sphere. Chunks around the camera have an infinite error.
+    pub fn select_chunks(&mut self, parameters: &LodParameters) -> Vec<(usize, usize)> {
+        let mut selected = Vec::new();
+
+        // Chunks at the 0th level have the coarsest triangles
+        let root_bounds: Vec<_> = self
+            .chunk_indices(0)
+            .map(|chunk_index| self.chunk_bounds(0, chunk_index))
+            .collect();
+        let max_error = root_bounds
+            .iter()
+            .map(|bounds| bounds.error)
+            .fold(0.0, f32::max);
+
+        let mut stack: Vec<(usize, usize)> = root_bounds
+            .iter()
+            .enumerate()
+            .rev()
+            .filter(|(_, bounds)| !parameters.is_culled(bounds, max_error))
+            .map(|(chunk_index, _)| (0, chunk_index))
+            .collect();
+
+        while let Some((level, chunk_index)) = stack.pop() {
+            if level + 1 == self.level_count()
+                || self.screen_space_error(level, chunk_index, parameters)
+                    <= parameters.error_threshold
+            {
+                selected.push((level, chunk_index));
+                continue;
+            }
+
+            for (subchunk_level, subchunk_index) in
+                self.subchunks(level, chunk_index).into_iter().rev()
+            {
+                if parameters.is_culled(
+                    &self.chunk_bounds(subchunk_level, subchunk_index),
+                    max_error,
+                ) {
+                    continue;
+                }
+
+                self.update_chunk(subchunk_level, subchunk_index);
+                stack.push((subchunk_level, subchunk_index));
+            }
+        }
+
+        selected
+    }
+
+    /// The error of a chunk, in pixels. See [`Self::select_chunks`].
+    fn screen_space_error(
+        &self,
+        level: usize,
+        chunk_index: usize,
+        parameters: &LodParameters,
+    ) -> f32 {
+        let bounds = parameters.rendered_bounds(&self.chunk_bounds(level, chunk_index));
+
+        let distance = parameters.camera_position.distance(bounds.center) - bounds.radius;
+
+        if distance <= 0.0 {
+            return f32::INFINITY;
+        }
+
+        bounds.error * parameters.pixels_per_unit(distance)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use glam::Mat4;
+
+    use super::*;
+    use crate::{SparseIcosphere, locate, triangle_count};
+
+    type Levels = IcosphereLevels<Vec3, SparseIcosphere<Vec3>>;
+
+    const LEVEL_COUNT: usize = 4;
+
+    fn parameters(camera_position: Vec3, error_threshold: f32) -> LodParameters {
+        LodParameters::new(camera_position, 1.0, 1080.0, error_threshold, 1.0)
+    }
+
+    /// The triangles at the deepest level covered by a chunk.
+    fn deepest_triangles(levels: &Levels, (level, chunk_index): (usize, usize)) -> Range<usize> {
+        let triangles = levels.chunk_triangles(level, chunk_index);
+        let generations = 2
+            * (levels.binning_depth_at_level(LEVEL_COUNT - 1)
+                - levels.binning_depth_at_level(level));
+
+        (triangles.start << generations)..(triangles.end << generations)
+    }
+
+    /// Checks that the chunks cover the sphere without overlapping.
+    fn assert_partition(levels: &Levels, selected: &[(usize, usize)]) {
+        let mut ranges: Vec<Range<usize>> = selected
+            .iter()
+            .map(|&chunk| deepest_triangles(levels, chunk))
+            .collect();
+        ranges.sort_by_key(|range| range.start);
+
+        assert_eq!(ranges.first().unwrap().start, 0);
+        assert_eq!(
+            ranges.last().unwrap().end,
+            triangle_count(levels.binning_depth_at_level(LEVEL_COUNT - 1))
+        );
+        assert!(ranges.windows(2).all(|pair| pair[0].end == pair[1].start));
+    }
+
+    /// The level of the selected chunk containing `direction`.
+    fn level_at(levels: &Levels, selected: &[(usize, usize)], direction: Vec3) -> usize {
+        let binning_depth = levels.binning_depth_at_level(LEVEL_COUNT - 1);
+        let triangle_index = locate::locate_triangle(direction, binning_depth);
+
+        selected
+            .iter()
+            .find(|&&chunk| deepest_triangles(levels, chunk).contains(&triangle_index))
+            .unwrap()
+            .0
+    }
+
+    #[test]
+    fn pixels_per_unit_follows_the_field_of_view() {
+        let mut parameters = parameters(Vec3::ZERO, 1.0);
+        parameters.vertical_fov = 2.0 * 0.5f32.atan();
+
+        // The viewport is as high as the distance
+        assert!((parameters.pixels_per_unit(4.0) - 1080.0 / 4.0).abs() < 1e-3);
+        assert!(parameters.pixels_per_unit(8.0) < parameters.pixels_per_unit(4.0));
+    }
+
+    #[test]
+    fn far_camera_selects_the_coarsest_chunks() {
+        let mut levels = Levels::new(0, LEVEL_COUNT, 1);
+        let selected = levels.select_chunks(&parameters(Vec3::Z * 1000.0, 1.0));
+
+        assert_eq!(
+            selected,
+            (0..20).map(|chunk| (0, chunk)).collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn chunks_under_the_camera_are_refined() {
+        let mut levels = Levels::new(0, LEVEL_COUNT, 1);
+        let below_camera = Vec3::new(0.3, 0.8, -0.5).normalize();
+        let selected = levels.select_chunks(&parameters(below_camera * 1.05, 20.0));
+
+        assert_partition(&levels, &selected);
+        assert_eq!(level_at(&levels, &selected, below_camera), LEVEL_COUNT - 1);
+        assert!(level_at(&levels, &selected, -below_camera) < LEVEL_COUNT - 1);
+
+        // Every selected chunk is generated
+        for &(level, chunk_index) in &selected {
+            let ico = levels.get(level);
+            assert!(
+                levels
+                    .chunk_triangles(level, chunk_index)
+                    .all(|triangle_index| ico.contains_triangle(triangle_index))
+            );
+        }
+    }
+
+    #[test]
+    fn lower_thresholds_select_finer_chunks() {
+        let camera_position = Vec3::new(-1.0, 0.2, 0.4) * 1.5;
+        let directions = [
+            Vec3::new(-1.0, 0.2, 0.4),
+            Vec3::new(0.0, 1.0, 0.0),
+            Vec3::new(1.0, -0.2, -0.4),
+        ];
+
+        let mut previous_levels = [0; 3];
+
+        for error_threshold in [100.0, 30.0, 10.0, 3.0] {
+            let mut levels = Levels::new(0, LEVEL_COUNT, 1);
+            let selected = levels.select_chunks(&parameters(camera_position, error_threshold));
+            assert_partition(&levels, &selected);
+
+            let chunk_levels = directions.map(|direction| level_at(&levels, &selected, direction));
+            for (level, previous_level) in chunk_levels.iter().zip(previous_levels) {
+                assert!(*level >= previous_level);
+            }
+
+            previous_levels = chunk_levels;
+        }
+
+        assert_eq!(previous_levels[0], LEVEL_COUNT - 1);
+    }
+
+    #[test]
+    fn culled_chunks_are_not_selected() {
+        let camera_position = Vec3::new(0.2, -0.4, 1.0).normalize() * 1.5;
+        let view = Mat4::look_at_rh(camera_position, Vec3::ZERO, Vec3::Y);
+        let projection = Mat4::perspective_rh(0.5, 1.0, 0.1, 10.0);
+        let frustum = Frustum::from_view_projection(projection * view);
+
+        let mut levels = Levels::new(0, LEVEL_COUNT, 1);
+        let parameters = parameters(camera_position, 10.0)
+            .with_frustum(frustum)
+            .with_horizon_culling();
+        let selected = levels.select_chunks(&parameters);
+
+        for &(level, chunk_index) in &selected {
+            let bounds = levels.chunk_bounds(level, chunk_index);
+
+            assert!(bounds.intersects_frustum(&frustum));
+            assert!(!bounds.is_behind_horizon(camera_position));
+        }
+
+        // The point at the center of the view is visible
+        let center = camera_position.normalize();
+        let triangle_index = locate::locate_triangle(center, LEVEL_COUNT - 1);
+        assert!(
+            selected
+                .iter()
+                .any(|&chunk| deepest_triangles(&levels, chunk).contains(&triangle_index))
+        );
+
+        // The far side isn't
+        let far_triangle = locate::locate_triangle(-center, LEVEL_COUNT - 1);
+        assert!(
+            !selected
+                .iter()
+                .any(|&chunk| deepest_triangles(&levels, chunk).contains(&far_triangle))
+        );
+    }
+}
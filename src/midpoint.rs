@@ -0,0 +1,369 @@
+use std::f64::consts::PI;
+
+use glam::{DVec2, DVec3, Vec3};
+
+use crate::{ICOSAHEDRON_TRIANGLES, icosahedron_positions, locate};
+
+/// How the vertex between two existing vertices is placed when subdividing.
+///
+/// Every rule is symmetric in its two inputs and only depends on their positions, so the triangle
+/// hierarchy is the same regardless of the rule, and lookups like [`locate::locate_triangle_with`]
+/// can reproduce the vertices of any subdivision.
+///
+/// To equalize triangle areas after generation instead, see
+/// [`crate::StaticIcosphere::relax_areas`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MidpointRule {
+    /// The midpoint of the chord between the two vertices, projected onto the sphere.
+    #[default]
+    Normalized,
+
+    /// The midpoint of the great circle arc between the two vertices. This is the same point as
+    /// [`Self::Normalized`] in exact arithmetic, but computed with trigonometry, so it can differ in
+    /// the last bits.
+    Slerp,
+
+    /// The midpoint on the flat faces of the regular icosahedron, mapped onto the sphere with Snyder's
+    /// equal-area polyhedral projection. Triangle areas vary about half as much on average, but the
+    /// shapes are more irregular, and the triangles around the centers of the icosahedron's faces
+    /// are still noticeably smaller. See [`crate::statistics::MeshStatistics`] to compare.
+    EqualArea,
+}
+
+impl MidpointRule {
+    /// The vertex between `a` and `b`, which must be unit length.
+    pub fn midpoint(self, a: Vec3, b: Vec3) -> Vec3 {
+        match self {
+            MidpointRule::Normalized => (a + b).normalize(),
+            MidpointRule::Slerp => {
+                let angle = a.dot(b).clamp(-1.0, 1.0).acos();
+
+                if angle.sin() == 0.0 {
+                    return (a + b).normalize();
+                }
+
+                (a + b) * ((angle / 2.0).sin() / angle.sin())
+            }
+            MidpointRule::EqualArea => equal_area_midpoint(a, b),
+        }
+    }
+
+    /// Encodes this rule as a number, e.g. for file headers.
+    pub fn to_u32(self) -> u32 {
+        match self {
+            MidpointRule::Normalized => 0,
+            MidpointRule::Slerp => 1,
+            MidpointRule::EqualArea => 2,
+        }
+    }
+
+    /// Inverse of [`Self::to_u32`].
+    pub fn from_u32(value: u32) -> Option<Self> {
+        match value {
+            0 => Some(MidpointRule::Normalized),
+            1 => Some(MidpointRule::Slerp),
+            2 => Some(MidpointRule::EqualArea),
+            _ => None,
+        }
+    }
+}
+
+/// Spherical angle at a vertex of the regular icosahedron between the center of a face and the
+/// midpoint of one of its edges. Five faces meet at each vertex, so this is half of 72°.
+const VERTEX_ANGLE: f64 = PI / 5.0;
+
+/// Angle at the center of a face between one of its vertices and the midpoint of an adjacent edge.
+const CENTER_ANGLE: f64 = PI / 3.0;
+
+/// Area of the spherical triangle between the center of a face, one of its vertices and the midpoint
+/// of an adjacent edge, which is a sixth of a face.
+const SUB_TRIANGLE_AREA: f64 = 4.0 * PI / 120.0;
+
+/// A face of the regular icosahedron, with everything needed to project to and from its flat
+/// counterpart.
+///
+/// The projection splits the face into six right triangles between the center, a vertex, and the
+/// midpoint of an adjacent edge. Within each, the azimuth around the center is chosen to preserve the
+/// area swept from the vertex, and the distance from the center is chosen to preserve the area
+/// enclosed, like in a Lambert azimuthal projection.
+pub(crate) struct Face {
+    center: DVec3,
+    corners: [DVec3; 3],
+
+    /// Spherical distance from the center to each corner.
+    corner_distance: f64,
+
+    /// Corners of the flat face, which has the same area as the spherical face and is centered on the
+    /// origin.
+    flat_corners: [DVec2; 3],
+}
+
+impl Face {
+    pub(crate) fn new(face_index: usize) -> Self {
+        let positions = icosahedron_positions();
+        let corners = ICOSAHEDRON_TRIANGLES[face_index].map(|i| positions[i as usize].as_dvec3());
+        let center = (corners[0] + corners[1] + corners[2]).normalize();
+
+        // An equilateral triangle with circumradius r has an area of r^2 * 3 * sqrt(3) / 4
+        let circumradius = (6.0 * SUB_TRIANGLE_AREA * 4.0 / (3.0 * 3.0f64.sqrt())).sqrt();
+        let flat_corners = [0.0, 1.0, 2.0].map(|k: f64| {
+            let angle = PI / 2.0 + k * 2.0 * PI / 3.0;
+            DVec2::new(angle.cos(), angle.sin()) * circumradius
+        });
+
+        Self {
+            center,
+            corners,
+            corner_distance: center.dot(corners[0]).acos(),
+            flat_corners,
+        }
+    }
+
+    /// Spherical area of the triangle between the center, a corner, and the point on the edge at the
+    /// given azimuth from the corner around the center.
+    fn swept_area(&self, azimuth: f64) -> f64 {
+        let (g, angle) = (self.corner_distance, VERTEX_ANGLE);
+        let edge_angle = (azimuth.sin() * angle.sin() * g.cos() - azimuth.cos() * angle.cos())
+            .clamp(-1.0, 1.0)
+            .acos();
+
+        azimuth + angle + edge_angle - PI
+    }
+
+    /// Direction in the plane tangent to the center of the face, pointing towards `point`.
+    fn tangent(&self, point: DVec3) -> DVec3 {
+        (point - self.center * self.center.dot(point)).normalize_or_zero()
+    }
+
+    /// The corner whose sub-triangles contain the direction, and the adjacent corner on the same side.
+    fn sector(&self, direction: DVec3) -> (usize, usize) {
+        let corner = (0..3)
+            .max_by(|&i, &j| {
+                let di = self.tangent(self.corners[i]).dot(direction);
+                let dj = self.tangent(self.corners[j]).dot(direction);
+                di.total_cmp(&dj)
+            })
+            .unwrap();
+
+        let (next, previous) = ((corner + 1) % 3, (corner + 2) % 3);
+        let side = if self.tangent(self.corners[next]).dot(direction)
+            >= self.tangent(self.corners[previous]).dot(direction)
+        {
+            next
+        } else {
+            previous
+        };
+
+        (corner, side)
+    }
+
+    /// Same as [`Self::sector`], but on the flat face.
+    fn flat_sector(&self, point: DVec2) -> (usize, usize) {
+        let corner = (0..3)
+            .max_by(|&i, &j| {
+                let di = self.flat_corners[i].dot(point);
+                let dj = self.flat_corners[j].dot(point);
+                di.total_cmp(&dj)
+            })
+            .unwrap();
+
+        let (next, previous) = ((corner + 1) % 3, (corner + 2) % 3);
+        let side = if self.flat_corners[next].dot(point) >= self.flat_corners[previous].dot(point) {
+            next
+        } else {
+            previous
+        };
+
+        (corner, side)
+    }
+
+    /// The point on the sphere at the given azimuth from `corner` around the center, towards `side`,
+    /// on the edge between them. Also returns the spherical distance from the center to that point.
+    fn edge_point(&self, corner: usize, side: usize, azimuth: f64) -> (DVec3, f64) {
+        let towards_corner = self.tangent(self.corners[corner]);
+        let mut perpendicular = self.center.cross(towards_corner);
+        if perpendicular.dot(self.corners[side]) < 0.0 {
+            perpendicular = -perpendicular;
+        }
+
+        let direction = towards_corner * azimuth.cos() + perpendicular * azimuth.sin();
+
+        // Intersect the great circle through the center in that direction with the edge
+        let circle = self.center.cross(direction);
+        let edge = self.corners[corner].cross(self.corners[side]);
+        let mut point = circle.cross(edge).normalize();
+        if point.dot(self.center) < 0.0 {
+            point = -point;
+        }
+
+        (point, self.center.dot(point).clamp(-1.0, 1.0).acos())
+    }
+
+    /// The point on the flat edge between `corner` and the midpoint towards `side` that sweeps the
+    /// given fraction of the flat sub-triangle's area.
+    fn flat_edge_point(&self, corner: usize, side: usize, fraction: f64) -> DVec2 {
+        let flat_corner = self.flat_corners[corner];
+        let flat_midpoint = (flat_corner + self.flat_corners[side]) / 2.0;
+
+        // The area swept from the corner grows linearly along the edge
+        flat_corner.lerp(flat_midpoint, fraction)
+    }
+
+    /// Projects a point on the sphere onto the flat face.
+    pub(crate) fn project(&self, point: DVec3) -> DVec2 {
+        let direction = self.tangent(point);
+        if direction == DVec3::ZERO {
+            return DVec2::ZERO;
+        }
+
+        let (corner, side) = self.sector(direction);
+        let azimuth = self
+            .tangent(self.corners[corner])
+            .dot(direction)
+            .clamp(-1.0, 1.0)
+            .acos()
+            .min(CENTER_ANGLE);
+
+        let (_, edge_distance) = self.edge_point(corner, side, azimuth);
+        let distance = self.center.dot(point).clamp(-1.0, 1.0).acos();
+
+        let fraction = (self.swept_area(azimuth) / SUB_TRIANGLE_AREA).clamp(0.0, 1.0);
+        let flat_edge = self.flat_edge_point(corner, side, fraction);
+
+        flat_edge * ((distance / 2.0).sin() / (edge_distance / 2.0).sin())
+    }
+
+    /// Inverse of [`Self::project`].
+    fn unproject(&self, point: DVec2) -> DVec3 {
+        if point == DVec2::ZERO {
+            return self.center;
+        }
+
+        let (corner, side) = self.flat_sector(point);
+
+        // Find where the ray from the center through the point crosses the flat edge
+        let flat_corner = self.flat_corners[corner];
+        let flat_midpoint = (flat_corner + self.flat_corners[side]) / 2.0;
+        let edge = flat_midpoint - flat_corner;
+        let fraction = (flat_corner.perp_dot(point) / point.perp_dot(edge)).clamp(0.0, 1.0);
+        let flat_edge = flat_corner + edge * fraction;
+
+        // The swept area increases with the azimuth, so it can be found by bisection
+        let target_area = fraction * SUB_TRIANGLE_AREA;
+        let (mut low, mut high) = (0.0, CENTER_ANGLE);
+
+        for _ in 0..64 {
+            let azimuth = (low + high) / 2.0;
+
+            if self.swept_area(azimuth) < target_area {
+                low = azimuth;
+            } else {
+                high = azimuth;
+            }
+        }
+
+        let azimuth = (low + high) / 2.0;
+        let (edge_point, edge_distance) = self.edge_point(corner, side, azimuth);
+
+        let ratio = (point.length() / flat_edge.length()).min(1.0);
+        let distance = 2.0 * (ratio * (edge_distance / 2.0).sin()).asin();
+
+        // Rotate from the center towards the edge point by the distance
+        let direction = self.tangent(edge_point);
+        self.center * distance.cos() + direction * distance.sin()
+    }
+}
+
+/// The midpoint of `a` and `b` on the flat face of the icosahedron containing both, mapped back onto
+/// the sphere.
+fn equal_area_midpoint(a: Vec3, b: Vec3) -> Vec3 {
+    let (face_index, _) = locate::locate_base_triangle(a + b);
+    let face = Face::new(face_index);
+
+    let midpoint = (face.project(a.as_dvec3()) + face.project(b.as_dvec3())) / 2.0;
+
+    face.unproject(midpoint).normalize().as_vec3()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::StaticIcosphere;
+
+    const RULES: [MidpointRule; 3] = [
+        MidpointRule::Normalized,
+        MidpointRule::Slerp,
+        MidpointRule::EqualArea,
+    ];
+
+    /// The ends of every edge of an icosphere of the given binning depth.
+    fn edges(binning_depth: usize) -> Vec<(Vec3, Vec3)> {
+        let ico = StaticIcosphere::<Vec3>::nth(binning_depth);
+
+        ico.triangles
+            .iter()
+            .flat_map(|&[a, b, c]| [(a, b), (b, c), (c, a)])
+            .map(|(a, b)| (ico.vertices[a as usize], ico.vertices[b as usize]))
+            .collect()
+    }
+
+    #[test]
+    fn midpoints_are_symmetric_and_between_the_ends() {
+        for (a, b) in edges(2) {
+            for rule in RULES {
+                let midpoint = rule.midpoint(a, b);
+
+                assert_eq!(midpoint, rule.midpoint(b, a));
+                assert!((midpoint.length() - 1.0).abs() < 1e-6);
+
+                // Roughly halfway, close to the great circle through the ends
+                let ratio = midpoint.angle_between(a) / midpoint.angle_between(b);
+                assert!((0.8..1.25).contains(&ratio));
+                assert!(midpoint.dot(a.cross(b).normalize()).abs() < 0.05);
+            }
+
+            let normalized = MidpointRule::Normalized.midpoint(a, b);
+            assert!(normalized.distance(MidpointRule::Slerp.midpoint(a, b)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn equal_area_keeps_the_icosahedron_edges() {
+        // The edges of the regular icosahedron are great circle arcs on both sides of the projection
+        for (a, b) in edges(0) {
+            let midpoint = MidpointRule::EqualArea.midpoint(a, b);
+
+            assert!(midpoint.distance(MidpointRule::Normalized.midpoint(a, b)) < 1e-6);
+        }
+    }
+
+    #[test]
+    fn projection_round_trips() {
+        for face_index in [0, 7, 19] {
+            let face = Face::new(face_index);
+
+            for point in edges(3).into_iter().map(|(a, _)| a.as_dvec3().normalize()) {
+                if locate::locate_base_triangle(point.as_vec3()).0 != face_index {
+                    continue;
+                }
+
+                assert!(face.unproject(face.project(point)).distance(point) < 1e-6);
+            }
+
+            // The corners are the corners of the flat face
+            for (corner, flat_corner) in face.corners.iter().zip(face.flat_corners) {
+                assert!(face.project(*corner).distance(flat_corner) < 1e-6);
+            }
+        }
+    }
+
+    #[test]
+    fn rules_round_trip_through_u32() {
+        for rule in RULES {
+            assert_eq!(MidpointRule::from_u32(rule.to_u32()), Some(rule));
+        }
+
+        assert_eq!(MidpointRule::from_u32(3), None);
+    }
+}
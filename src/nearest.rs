@@ -0,0 +1,255 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashSet},
+};
+
+use glam::Vec3;
+
+use crate::{Icosphere, IcosphereVertex};
+
+/// Finds the vertex closest to `direction`, as its index in [`Icosphere::vertices`] and the angle
+/// between them in radians. Returns `None` if the icosphere has no vertices.
+///
+/// The search starts at the closest corner of the triangle containing `direction` (see
+/// [`Icosphere::locate_triangle`]) and walks to whichever neighbor is closer until none is. The mesh
+/// is close to a Delaunay triangulation, where such a walk always ends at the closest vertex, and the
+/// walk also corrects for vertices moved off the positions the hierarchy predicts, like by
+/// [`crate::StaticIcosphere::relax_areas`].
+///
+/// If the icosphere is sparse and the containing triangle isn't generated, the closest generated
+/// vertex may be anywhere, so every vertex is checked instead.
+pub fn nearest_vertex<T, S>(ico: &S, direction: Vec3) -> Option<(usize, f32)>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    let direction = direction.normalize();
+    let closeness = |vertex_index: usize| closeness(ico, direction, vertex_index);
+
+    let triangle_index = ico.locate_triangle(direction);
+
+    let mut nearest = if ico.contains_triangle(triangle_index) {
+        ico.triangle(triangle_index)
+            .map(|vertex_index| vertex_index as usize)
+            .into_iter()
+            .max_by(|&a, &b| closeness(a).total_cmp(&closeness(b)))?
+    } else {
+        (0..ico.vertices().len()).max_by(|&a, &b| closeness(a).total_cmp(&closeness(b)))?
+    };
+
+    while let Some(closer) = ico.neighbors()[&nearest]
+        .iter()
+        .copied()
+        .filter(|&neighbor| closeness(neighbor) > closeness(nearest))
+        .max_by(|&a, &b| closeness(a).total_cmp(&closeness(b)))
+    {
+        nearest = closer;
+    }
+
+    Some((nearest, angle(closeness(nearest))))
+}
+
+/// Finds the `k` vertices closest to `direction`, from closest to farthest, along with their angles
+/// to it in radians. Returns fewer if the icosphere has fewer vertices.
+///
+/// Starting from [`nearest_vertex`], the neighbors of every vertex found are the candidates for the
+/// next one. In a Delaunay triangulation, the `k` closest vertices are always connected this way.
+///
+/// If the icosphere is sparse and not completely generated, vertices can be closer across a gap
+/// than through the generated triangles, so every vertex is checked instead.
+pub fn k_nearest_vertices<T, S>(ico: &S, direction: Vec3, k: usize) -> Vec<(usize, f32)>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    let direction = direction.normalize();
+
+    let Some((nearest, _)) = nearest_vertex(ico, direction).filter(|_| k > 0) else {
+        return Vec::new();
+    };
+
+    if ico.allocated_triangle_count() < ico.total_triangle_count() {
+        return scan_nearest_vertices(ico, direction, k);
+    }
+
+    let mut found = Vec::with_capacity(k);
+    let mut visited = HashSet::from([nearest]);
+    let mut candidates = BinaryHeap::from([Candidate {
+        closeness: closeness(ico, direction, nearest),
+        vertex_index: nearest,
+    }]);
+
+    while let Some(candidate) = candidates.pop() {
+        found.push((candidate.vertex_index, angle(candidate.closeness)));
+
+        if found.len() == k {
+            break;
+        }
+
+        for &neighbor in &ico.neighbors()[&candidate.vertex_index] {
+            if visited.insert(neighbor) {
+                candidates.push(Candidate {
+                    closeness: closeness(ico, direction, neighbor),
+                    vertex_index: neighbor,
+                });
+            }
+        }
+    }
+
+    found
+}
+
+/// The `k` vertices closest to `direction`, found by checking every vertex.
+fn scan_nearest_vertices<T, S>(ico: &S, direction: Vec3, k: usize) -> Vec<(usize, f32)>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    let mut candidates: Vec<Candidate> = (0..ico.vertices().len())
+        .map(|vertex_index| Candidate {
+            closeness: closeness(ico, direction, vertex_index),
+            vertex_index,
+        })
+        .collect();
+
+    candidates.sort_unstable_by(|a, b| b.cmp(a));
+
+    candidates
+        .into_iter()
+        .take(k)
+        .map(|candidate| (candidate.vertex_index, angle(candidate.closeness)))
+        .collect()
+}
+
+/// The negated squared distance between `direction` and a vertex on the unit sphere, which is larger
+/// for closer vertices. Unlike the cosine of the angle between them, it stays precise for vertices
+/// that are very close.
+fn closeness<T, S>(ico: &S, direction: Vec3, vertex_index: usize) -> f32
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    -ico.vertices()[vertex_index]
+        .position()
+        .normalize()
+        .distance_squared(direction)
+}
+
+fn angle(closeness: f32) -> f32 {
+    2.0 * ((-closeness).sqrt() / 2.0).min(1.0).asin()
+}
+
+/// A vertex that may be among the closest, ordered by closeness so that [`BinaryHeap`] pops the
+/// closest first.
+struct Candidate {
+    closeness: f32,
+    vertex_index: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.closeness
+            .total_cmp(&other.closeness)
+            .then(other.vertex_index.cmp(&self.vertex_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere};
+
+    /// Directions all over the sphere, along a Fibonacci spiral.
+    fn directions(count: usize) -> impl Iterator<Item = Vec3> {
+        (0..count).map(move |i| {
+            let y = 1.0 - 2.0 * (i as f32 + 0.5) / count as f32;
+            let longitude = i as f32 * 2.399_963;
+            let radius = (1.0 - y * y).sqrt();
+
+            Vec3::new(radius * longitude.cos(), y, radius * longitude.sin())
+        })
+    }
+
+    /// The angles to the `k` closest vertices, found by sorting all of them.
+    fn scanned_angles<S: Icosphere<Vec3>>(ico: &S, direction: Vec3, k: usize) -> Vec<f32> {
+        let mut angles: Vec<f32> = (0..ico.vertices().len())
+            .map(|vertex_index| angle(closeness(ico, direction, vertex_index)))
+            .collect();
+        angles.sort_by(f32::total_cmp);
+        angles.truncate(k);
+
+        angles
+    }
+
+    /// Whether the angles are the same, up to rounding. Vertices that are almost as close may be found
+    /// in either order.
+    fn same_angles(a: &[f32], b: &[f32]) -> bool {
+        a.len() == b.len() && a.iter().zip(b).all(|(a, b)| (a - b).abs() < 1e-6)
+    }
+
+    fn assert_matches_scan<S: Icosphere<Vec3>>(ico: &S) {
+        for direction in directions(500) {
+            let (nearest, nearest_angle) = nearest_vertex(ico, direction).unwrap();
+            let nearest_closeness = closeness(ico, direction.normalize(), nearest);
+            assert_eq!(nearest_angle, angle(nearest_closeness));
+            assert!(same_angles(
+                &[nearest_angle],
+                &scanned_angles(ico, direction, 1)
+            ));
+
+            for k in [1, 7, 20] {
+                let found = k_nearest_vertices(ico, direction, k);
+                let angles: Vec<f32> = found.iter().map(|&(_, angle)| angle).collect();
+                assert!(same_angles(&angles, &scanned_angles(ico, direction, k)));
+
+                let distinct: HashSet<usize> = found.iter().map(|&(index, _)| index).collect();
+                assert_eq!(distinct.len(), found.len());
+            }
+        }
+    }
+
+    /// A sparse icosphere of binning depth 3 with a few scattered chunks generated.
+    fn partial_icosphere() -> SparseIcosphere<Vec3> {
+        let previous = SparseIcosphere::filled(2);
+        let mut ico = SparseIcosphere::empty(3);
+
+        for parent_index in (0..crate::triangle_count(2)).step_by(9) {
+            ico.subdivide_chunk(&previous, parent_index);
+        }
+
+        ico
+    }
+
+    #[test]
+    fn static_icosphere_matches_scan() {
+        assert_matches_scan(&StaticIcosphere::<Vec3>::nth(3));
+    }
+
+    #[test]
+    fn partial_icosphere_matches_scan() {
+        assert_matches_scan(&partial_icosphere());
+    }
+
+    #[test]
+    fn fewer_vertices_than_k() {
+        let ico = StaticIcosphere::<Vec3>::nth(0);
+
+        assert_eq!(k_nearest_vertices(&ico, Vec3::Y, 20).len(), 12);
+        assert!(k_nearest_vertices(&ico, Vec3::Y, 0).is_empty());
+        assert!(nearest_vertex(&SparseIcosphere::<Vec3>::empty(2), Vec3::Y).is_none());
+    }
+}
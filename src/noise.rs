@@ -0,0 +1,364 @@
+use std::{fmt, marker::PhantomData};
+
+use glam::Vec3;
+
+use crate::IcosphereVertex;
+
+/// Skews space onto the simplex grid, `1 / 3` in three dimensions.
+const SKEW: f32 = 1.0 / 3.0;
+
+/// Unskews the simplex grid back into space, `1 / 6` in three dimensions.
+const UNSKEW: f32 = 1.0 / 6.0;
+
+/// Scales the sum of corner contributions to roughly `-1.0..=1.0`.
+const SIMPLEX_SCALE: f32 = 32.0;
+
+/// Gradients at the corners of the simplex grid, towards the edges of a cube.
+const GRADIENTS: [Vec3; 12] = [
+    Vec3::new(1.0, 1.0, 0.0),
+    Vec3::new(-1.0, 1.0, 0.0),
+    Vec3::new(1.0, -1.0, 0.0),
+    Vec3::new(-1.0, -1.0, 0.0),
+    Vec3::new(1.0, 0.0, 1.0),
+    Vec3::new(-1.0, 0.0, 1.0),
+    Vec3::new(1.0, 0.0, -1.0),
+    Vec3::new(-1.0, 0.0, -1.0),
+    Vec3::new(0.0, 1.0, 1.0),
+    Vec3::new(0.0, -1.0, 1.0),
+    Vec3::new(0.0, 1.0, -1.0),
+    Vec3::new(0.0, -1.0, -1.0),
+];
+
+/// Offsets between the three samples of a domain warp, far enough apart to be uncorrelated.
+const WARP_OFFSETS: [Vec3; 3] = [
+    Vec3::new(0.0, 0.0, 0.0),
+    Vec3::new(5.2, 1.3, 2.8),
+    Vec3::new(1.7, 9.2, 4.1),
+];
+
+/// Seeded 3D simplex noise. The same seed always gives the same noise on every platform, since it
+/// only uses integer hashing and basic float arithmetic, which is exactly rounded, and none of the
+/// functions like `powi` or `sin` whose precision depends on the platform.
+///
+/// Sampling unit directions gives noise on the sphere without seams or pinching at the poles, so it
+/// can be fed straight from [`IcosphereVertex::from_position`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Noise {
+    pub seed: u32,
+}
+
+impl Noise {
+    pub const fn new(seed: u32) -> Self {
+        Self { seed }
+    }
+
+    /// Simplex noise at a point, roughly in `-1.0..=1.0`. Features are about one unit apart.
+    pub fn sample(&self, point: Vec3) -> f32 {
+        // Cell of the skewed grid the point is in
+        let skew = (point.x + point.y + point.z) * SKEW;
+        let cell = (point + skew).floor();
+        let unskew = (cell.x + cell.y + cell.z) * UNSKEW;
+        let offset = point - (cell - unskew);
+
+        // The cell is split into six simplices, and the order of the offsets picks one. Its corners
+        // are reached by stepping along the axes from the largest offset to the smallest.
+        let (first, second) = if offset.x >= offset.y {
+            if offset.y >= offset.z {
+                (Vec3::X, Vec3::X + Vec3::Y)
+            } else if offset.x >= offset.z {
+                (Vec3::X, Vec3::X + Vec3::Z)
+            } else {
+                (Vec3::Z, Vec3::X + Vec3::Z)
+            }
+        } else if offset.y < offset.z {
+            (Vec3::Z, Vec3::Y + Vec3::Z)
+        } else if offset.x < offset.z {
+            (Vec3::Y, Vec3::Y + Vec3::Z)
+        } else {
+            (Vec3::Y, Vec3::X + Vec3::Y)
+        };
+
+        [Vec3::ZERO, first, second, Vec3::ONE]
+            .into_iter()
+            .enumerate()
+            .map(|(corner, step)| {
+                let corner_offset = offset - step + UNSKEW * corner as f32;
+                let falloff = 0.6 - corner_offset.length_squared();
+
+                if falloff <= 0.0 {
+                    return 0.0;
+                }
+
+                // Multiplied out rather than `powi`, whose result can differ between platforms
+                let falloff_squared = falloff * falloff;
+                let gradient = self.gradient(cell + step);
+                falloff_squared * falloff_squared * gradient.dot(corner_offset)
+            })
+            .sum::<f32>()
+            * SIMPLEX_SCALE
+    }
+
+    /// Fractal Brownian motion: octaves of noise added together, each one finer and weaker than the
+    /// last. Roughly in `-1.0..=1.0`.
+    pub fn fbm(&self, point: Vec3, octaves: &Octaves) -> f32 {
+        octaves.sum(self, point, |noise| noise)
+    }
+
+    /// Ridged multifractal noise: octaves of inverted absolute noise, making sharp ridges where the
+    /// noise crosses zero. Finer octaves are only added near the ridges of coarser ones, so valleys
+    /// stay smooth. In `0.0..=1.0`.
+    pub fn ridged(&self, point: Vec3, octaves: &Octaves) -> f32 {
+        let mut weight = 1.0;
+
+        octaves.sum(self, point, |noise| {
+            let ridge = 1.0 - noise.abs();
+            let signal = ridge * ridge * weight;
+            weight = (signal * 2.0).clamp(0.0, 1.0);
+
+            signal
+        })
+    }
+
+    /// Moves the point by fractal noise, up to roughly `strength` in every axis. Sampling other noise
+    /// at the warped point gives swirling, less regular shapes.
+    pub fn warp(&self, point: Vec3, octaves: &Octaves, strength: f32) -> Vec3 {
+        let [x, y, z] = WARP_OFFSETS.map(|offset| self.fbm(point + offset, octaves));
+
+        point + Vec3::new(x, y, z) * strength
+    }
+
+    /// A pseudorandom gradient for a corner of the simplex grid.
+    fn gradient(&self, corner: Vec3) -> Vec3 {
+        let [x, y, z] = corner.to_array().map(|coordinate| coordinate as i32 as u32);
+
+        let mut hash = self.seed
+            ^ x.wrapping_mul(0x8da6_b343)
+            ^ y.wrapping_mul(0xd816_3841)
+            ^ z.wrapping_mul(0xcb1a_b31f);
+
+        // Finalizer of MurmurHash3, so that neighboring corners get unrelated gradients
+        hash ^= hash >> 16;
+        hash = hash.wrapping_mul(0x85eb_ca6b);
+        hash ^= hash >> 13;
+        hash = hash.wrapping_mul(0xc2b2_ae35);
+        hash ^= hash >> 16;
+
+        GRADIENTS[hash as usize % GRADIENTS.len()]
+    }
+}
+
+/// How octaves of fractal noise are layered. See [`Noise::fbm`] and [`Noise::ridged`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Octaves {
+    /// The number of octaves.
+    pub count: usize,
+
+    /// Frequency of the first octave. On the unit sphere, this is roughly how many features fit
+    /// across a radian.
+    pub frequency: f32,
+
+    /// How much the frequency is multiplied by for every octave.
+    pub lacunarity: f32,
+
+    /// How much the amplitude is multiplied by for every octave.
+    pub gain: f32,
+}
+
+impl Octaves {
+    pub const fn new(count: usize, frequency: f32) -> Self {
+        Self {
+            count,
+            frequency,
+            lacunarity: 2.0,
+            gain: 0.5,
+        }
+    }
+
+    /// Adds up `octave(noise)` for every octave, weighted by its amplitude, and divided by
+    /// the total amplitude. Every octave samples noise with a different seed, so they don't line up.
+    fn sum(&self, noise: &Noise, point: Vec3, mut octave: impl FnMut(f32) -> f32) -> f32 {
+        let mut frequency = self.frequency;
+        let mut amplitude = 1.0;
+        let mut sum = 0.0;
+        let mut total_amplitude = 0.0;
+
+        for index in 0..self.count {
+            let octave_noise = Noise::new(noise.seed.wrapping_add(index as u32));
+
+            sum += octave(octave_noise.sample(point * frequency)) * amplitude;
+            total_amplitude += amplitude;
+
+            frequency *= self.lacunarity;
+            amplitude *= self.gain;
+        }
+
+        if total_amplitude > 0.0 {
+            sum / total_amplitude
+        } else {
+            0.0
+        }
+    }
+}
+
+/// A complete recipe for a planet: warped continents with ridged mountains on land, and moisture.
+///
+/// Heights are in `-1.0..=1.0`, with the sea at zero, and can be scaled into a
+/// [`crate::displacement::Heightmap`]. Moisture is in `0.0..=1.0`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Terrain {
+    pub seed: u32,
+
+    /// Shape of the continents.
+    pub continents: Octaves,
+
+    /// Shape of the mountains, which rise from the coasts towards the middle of continents.
+    pub mountains: Octaves,
+
+    /// Shape of the warp applied to continents and mountains. See [`Noise::warp`].
+    pub warp: Octaves,
+    pub warp_strength: f32,
+
+    /// How much of the height comes from mountains, in `0.0..=1.0`.
+    pub mountain_weight: f32,
+
+    /// Shape of moisture, which is independent of height.
+    pub moisture: Octaves,
+}
+
+impl Terrain {
+    /// Earth-like continents from the seed, with Earth-like defaults for every other setting.
+    pub const fn new(seed: u32) -> Self {
+        Self {
+            seed,
+            continents: Octaves::new(6, 1.5),
+            mountains: Octaves::new(5, 4.0),
+            warp: Octaves::new(3, 1.0),
+            warp_strength: 0.4,
+            mountain_weight: 0.5,
+            moisture: Octaves::new(4, 2.0),
+        }
+    }
+
+    /// Height in the given direction. See [`Self`].
+    pub fn height(&self, direction: Vec3) -> f32 {
+        let point = self
+            .noise(0)
+            .warp(direction, &self.warp, self.warp_strength);
+
+        let continents = self.noise(1).fbm(point, &self.continents);
+        let mountains = self.noise(2).ridged(point, &self.mountains);
+
+        // Mountains fade in above sea level, so coasts stay low
+        let inland = (continents * 4.0).clamp(0.0, 1.0);
+
+        (continents * (1.0 - self.mountain_weight) + mountains * inland * self.mountain_weight)
+            .clamp(-1.0, 1.0)
+    }
+
+    /// Moisture in the given direction. See [`Self`].
+    pub fn moisture(&self, direction: Vec3) -> f32 {
+        (self.noise(3).fbm(direction, &self.moisture) * 0.5 + 0.5).clamp(0.0, 1.0)
+    }
+
+    /// Noise for one part of the terrain. The seeds of different parts are far apart, so their
+    /// octaves don't share a seed.
+    fn noise(&self, part: u32) -> Noise {
+        Noise::new(self.seed.wrapping_add(part.wrapping_mul(0x9e37_79b9)))
+    }
+}
+
+/// The terrain a [`PlanetVertex`] is sampled from. Implement this on an empty type to pick a seed or
+/// tune the terrain.
+pub trait Planet {
+    const TERRAIN: Terrain;
+}
+
+/// A vertex with the height and moisture of a [`Planet`]'s terrain, sampled when the vertex is
+/// created.
+///
+/// [`IcosphereVertex::position`] stays on the unit sphere. To also move the vertex by its height, use
+/// [`Terrain::height`] in a [`crate::displacement::Heightmap`] instead.
+pub struct PlanetVertex<P: Planet> {
+    pub position: Vec3,
+    pub height: f32,
+    pub moisture: f32,
+
+    _phantom: PhantomData<fn() -> P>,
+}
+
+impl<P: Planet> IcosphereVertex for PlanetVertex<P> {
+    fn position(&self) -> Vec3 {
+        self.position
+    }
+
+    fn from_position(position: Vec3, _binning_depth: usize) -> Self {
+        let direction = position.normalize();
+
+        Self {
+            position,
+            height: P::TERRAIN.height(direction),
+            moisture: P::TERRAIN.moisture(direction),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+// Derives would require the planet to implement these too
+
+impl<P: Planet> Clone for PlanetVertex<P> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<P: Planet> Copy for PlanetVertex<P> {}
+
+impl<P: Planet> PartialEq for PlanetVertex<P> {
+    fn eq(&self, other: &Self) -> bool {
+        self.position == other.position
+            && self.height == other.height
+            && self.moisture == other.moisture
+    }
+}
+
+impl<P: Planet> fmt::Debug for PlanetVertex<P> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("PlanetVertex")
+            .field("position", &self.position)
+            .field("height", &self.height)
+            .field("moisture", &self.moisture)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The exact bits, so any change in the arithmetic that could make results differ between
+    /// platforms or versions shows up.
+    #[test]
+    fn same_bits_everywhere() {
+        let noise = Noise::new(7);
+        let octaves = Octaves::new(4, 2.0);
+        let point = Vec3::new(0.3, -0.5, 0.8);
+
+        assert_eq!(noise.sample(point).to_bits(), 0x3f04_572c);
+        assert_eq!(noise.fbm(point, &octaves).to_bits(), 0xbd76_22c0);
+        assert_eq!(noise.ridged(point, &octaves).to_bits(), 0x3e5b_0dc0);
+        assert_eq!(
+            Terrain::new(42).height(point.normalize()).to_bits(),
+            0xbe7a_1230
+        );
+    }
+
+    #[test]
+    fn seeds_differ() {
+        let point = Vec3::new(0.3, -0.5, 0.8);
+
+        assert_ne!(Noise::new(1).sample(point), Noise::new(2).sample(point));
+    }
+}
@@ -0,0 +1,263 @@
+use std::collections::{HashMap, HashSet};
+
+use rayon::prelude::*;
+
+use crate::{IcosphereVertex, StaticIcosphere, adjacency, midpoint::MidpointRule};
+
+impl<T: IcosphereVertex + Send + Sync> StaticIcosphere<T> {
+    /// Same as [`Self::nth`], but subdivides in parallel. See [`Self::par_subdivide`].
+    pub fn par_nth(binning_depth: usize) -> Self {
+        Self::par_nth_with(binning_depth, MidpointRule::Normalized)
+    }
+
+    /// Same as [`Self::nth_with`], but subdivides in parallel. See [`Self::par_subdivide`].
+    pub fn par_nth_with(binning_depth: usize, midpoint_rule: MidpointRule) -> Self {
+        let mut ico = Self::regular();
+        ico.midpoint_rule = midpoint_rule;
+
+        for _ in 0..binning_depth {
+            ico = ico.par_subdivide();
+        }
+
+        ico
+    }
+
+    /// Same as [`crate::Icosphere::subdivide`], but splits the triangles on the rayon thread pool. The
+    /// result is identical, down to the order of the vertices.
+    ///
+    /// The serial subdivision creates the midpoint of an edge the first time it sees it, which is
+    /// from the triangle with the lower index of the two sharing it. Every triangle finds the edges
+    /// it creates midpoints for from the triangle hierarchy (see [`adjacency::edge_neighbor`]), and
+    /// counting them gives the index of every midpoint before any of them is created.
+    pub fn par_subdivide(&self) -> Self {
+        let binning_depth = self.binning_depth;
+        let position = |vertex_index: u32| self.vertices[vertex_index as usize].position();
+
+        // For every edge of every triangle, the triangle across it, the index of the edge there, and
+        // whether this triangle creates the midpoint
+        let edges: Vec<[(usize, usize, bool); 3]> = (0..self.triangles.len())
+            .into_par_iter()
+            .map(|triangle_index| {
+                [0, 1, 2].map(|edge| {
+                    let (neighbor, neighbor_edge) =
+                        adjacency::edge_neighbor(triangle_index, edge, binning_depth);
+
+                    (neighbor, neighbor_edge, triangle_index < neighbor)
+                })
+            })
+            .collect();
+        let edges = &edges;
+
+        // Index of the first midpoint created by every triangle
+        let first_midpoints: Vec<usize> = edges
+            .iter()
+            .scan(self.vertices.len(), |next, edges| {
+                let first = *next;
+                *next += edges.iter().filter(|&&(_, _, owned)| owned).count();
+
+                Some(first)
+            })
+            .collect();
+
+        let midpoint_index = |triangle_index: usize, edge: usize| {
+            let owned_before = edges[triangle_index][..edge]
+                .iter()
+                .filter(|&&(_, _, owned)| owned)
+                .count();
+
+            first_midpoints[triangle_index] + owned_before
+        };
+
+        let segment_midpoints: Vec<[u32; 3]> = (0..self.triangles.len())
+            .into_par_iter()
+            .map(|triangle_index| {
+                [0, 1, 2].map(|edge| {
+                    let (neighbor, neighbor_edge, owned) = edges[triangle_index][edge];
+
+                    if owned {
+                        midpoint_index(triangle_index, edge) as u32
+                    } else {
+                        midpoint_index(neighbor, neighbor_edge) as u32
+                    }
+                })
+            })
+            .collect();
+
+        // Owned edges as the triangle, the edge, and the sorted vertex indices of its ends, in the
+        // order their midpoints are created
+        let owned_edges = || {
+            (0..self.triangles.len())
+                .into_par_iter()
+                .flat_map_iter(|triangle_index| {
+                    let triangle = self.triangles[triangle_index];
+
+                    (0..3)
+                        .filter(move |&edge| edges[triangle_index][edge].2)
+                        .map(move |edge| {
+                            let (i, j) = (triangle[edge], triangle[(edge + 1) % 3]);
+                            (triangle_index, edge, (i.min(j), i.max(j)))
+                        })
+                })
+        };
+
+        let mut vertices = Vec::with_capacity(crate::vertex_count(binning_depth + 1));
+        vertices.extend_from_slice(&self.vertices);
+        vertices.par_extend(owned_edges().map(|(_, _, (i, j))| {
+            let midpoint = self.midpoint_rule.midpoint(position(i), position(j));
+            T::from_position(midpoint, binning_depth + 1)
+        }));
+
+        let triangles: Vec<[u32; 3]> = self
+            .triangles
+            .par_iter()
+            .zip(&segment_midpoints)
+            .flat_map_iter(|(&[a, b, c], &[d, e, f])| [[a, d, f], [b, e, d], [c, f, e], [d, e, f]])
+            .collect();
+
+        let midpoints: HashMap<(usize, usize), usize> = owned_edges()
+            .map(|(triangle_index, edge, (i, j))| {
+                let key = (i as usize, j as usize);
+                (key, segment_midpoints[triangle_index][edge] as usize)
+            })
+            .collect();
+
+        // Old vertices are connected to the midpoints of their old edges, and every midpoint to the
+        // ends of its edge and the other midpoints of both triangles sharing it
+        let old_neighbors = self.neighbors.par_iter().map(|(&i, neighbors)| {
+            let neighbors: HashSet<usize> = neighbors
+                .iter()
+                .map(|&j| midpoints[&(i.min(j), i.max(j))])
+                .collect();
+
+            (i, neighbors)
+        });
+
+        let new_neighbors = owned_edges().map(|(triangle_index, edge, (i, j))| {
+            let (neighbor, neighbor_edge, _) = edges[triangle_index][edge];
+            let others = |triangle_index: usize, edge: usize| {
+                let segment_midpoints = segment_midpoints[triangle_index];
+
+                [1, 2].map(|offset| segment_midpoints[(edge + offset) % 3] as usize)
+            };
+
+            let neighbors: HashSet<usize> = [i as usize, j as usize]
+                .into_iter()
+                .chain(others(triangle_index, edge))
+                .chain(others(neighbor, neighbor_edge))
+                .collect();
+
+            (segment_midpoints[triangle_index][edge] as usize, neighbors)
+        });
+
+        let neighbors = old_neighbors.chain(new_neighbors).collect();
+
+        Self {
+            vertices,
+            triangles,
+            neighbors,
+            binning_depth: binning_depth + 1,
+            midpoint_rule: self.midpoint_rule,
+            midpoints,
+        }
+    }
+}
+
+/// Creates the midpoint vertices deferred by a sparse icosphere, given as `[midpoint, i, j]` where
+/// the midpoint is placed between the vertices at `i` and `j`.
+pub(crate) fn create_midpoints<T: IcosphereVertex + Send + Sync>(
+    vertices: &mut [T],
+    deferred: &[[usize; 3]],
+    midpoint_rule: MidpointRule,
+    binning_depth: usize,
+) {
+    let created: Vec<T> = deferred
+        .par_iter()
+        .map(|&[_, i, j]| {
+            let midpoint = midpoint_rule.midpoint(vertices[i].position(), vertices[j].position());
+            T::from_position(midpoint, binning_depth)
+        })
+        .collect();
+
+    for (&[midpoint_index, _, _], vertex) in deferred.iter().zip(created) {
+        vertices[midpoint_index] = vertex;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{Icosphere, SparseIcosphere, levels::IcosphereLevels};
+
+    #[test]
+    fn par_nth_matches_nth() {
+        for midpoint_rule in [MidpointRule::Normalized, MidpointRule::EqualArea] {
+            for binning_depth in 0..5 {
+                let serial = StaticIcosphere::<Vec3>::nth_with(binning_depth, midpoint_rule);
+                let parallel = StaticIcosphere::<Vec3>::par_nth_with(binning_depth, midpoint_rule);
+
+                assert_eq!(parallel.vertices, serial.vertices);
+                assert_eq!(parallel.triangles, serial.triangles);
+                assert_eq!(parallel.neighbors, serial.neighbors);
+                assert_eq!(parallel.midpoints, serial.midpoints);
+            }
+        }
+    }
+
+    #[test]
+    fn par_subdivide_chunks_matches_subdivide_chunk() {
+        let previous = SparseIcosphere::<Vec3>::filled(2);
+        let parent_indices: Vec<usize> = (0..320).filter(|i| i % 3 != 1).rev().collect();
+
+        let mut serial = SparseIcosphere::empty(3);
+        for &parent_index in &parent_indices {
+            serial.subdivide_chunk(&previous, parent_index);
+        }
+
+        let mut parallel = SparseIcosphere::empty(3);
+        assert!(parallel.par_subdivide_chunks(&previous, &parent_indices));
+        assert!(!parallel.par_subdivide_chunks(&previous, &parent_indices));
+
+        assert_eq!(parallel.vertices, serial.vertices);
+        assert_eq!(parallel.triangles, serial.triangles);
+        assert_eq!(parallel.neighbors, serial.neighbors);
+    }
+
+    #[test]
+    fn par_update_chunks_matches_update_chunk() {
+        type Levels = IcosphereLevels<Vec3, SparseIcosphere<Vec3>>;
+
+        let chunk_indices = [0, 5, 6, 77, 200, 319];
+
+        let mut serial = Levels::new(1, 3, 2);
+        for &chunk_index in &chunk_indices {
+            serial.update_chunk(2, chunk_index);
+        }
+
+        let mut parallel = Levels::new(1, 3, 2);
+        assert!(parallel.par_update_chunks(2, &chunk_indices));
+        assert!(!parallel.par_update_chunks(2, &chunk_indices));
+
+        // Vertices are created in another order, but the same triangles end up in the same places
+        for level in 0..3 {
+            let (serial, parallel) = (serial.get(level), parallel.get(level));
+            let triangle_indices = serial.allocated_triangle_indices();
+            assert_eq!(parallel.allocated_triangle_indices(), triangle_indices);
+
+            for triangle_index in triangle_indices {
+                let positions = |ico: &SparseIcosphere<Vec3>| {
+                    ico.triangle(triangle_index)
+                        .map(|vertex_index| ico.vertices[vertex_index as usize])
+                };
+
+                assert_eq!(positions(parallel), positions(serial));
+            }
+        }
+
+        // The same every time
+        let mut again = Levels::new(1, 3, 2);
+        again.par_update_chunks(2, &chunk_indices);
+        assert_eq!(again.get(2).vertices, parallel.get(2).vertices);
+    }
+}
@@ -0,0 +1,469 @@
+use std::{
+    cmp::Ordering,
+    collections::{BinaryHeap, HashMap, HashSet},
+};
+
+use glam::Vec3;
+
+use crate::{Icosphere, IcosphereVertex, adjacency};
+
+/// A path through the vertex or triangle graph of an icosphere.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Path {
+    /// Vertex or triangle indices, from the start to the goal, both included.
+    pub indices: Vec<usize>,
+
+    /// The sum of the costs of every step.
+    pub cost: f32,
+}
+
+/// Finds the cheapest path between two vertices along the edges of the icosphere, with Dijkstra's
+/// algorithm. Returns `None` if the goal can't be reached.
+///
+/// `cost(from, to)` is the cost of stepping between two neighboring vertices, which must not be
+/// negative, or `None` if the step isn't allowed. For the shortest path on the sphere, use
+/// [`vertex_angle`]. If this icosphere is sparse, paths only go through generated vertices.
+pub fn vertex_dijkstra<T, S>(
+    ico: &S,
+    start: usize,
+    goal: usize,
+    cost: impl FnMut(usize, usize) -> Option<f32>,
+) -> Option<Path>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    vertex_astar(ico, start, goal, cost, |_| 0.0)
+}
+
+/// Same as [`vertex_dijkstra`], but with A*, which explores fewer vertices by estimating the
+/// remaining cost with `heuristic(vertex)`.
+///
+/// The path is only guaranteed to be the cheapest if the heuristic never overestimates. If every
+/// step costs at least its [`vertex_angle`] times some factor, the angle to the goal times that
+/// factor works.
+pub fn vertex_astar<T, S>(
+    ico: &S,
+    start: usize,
+    goal: usize,
+    cost: impl FnMut(usize, usize) -> Option<f32>,
+    heuristic: impl FnMut(usize) -> f32,
+) -> Option<Path>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    let neighbors = |vertex_index: usize| {
+        ico.neighbors()
+            .get(&vertex_index)
+            .into_iter()
+            .flatten()
+            .copied()
+            .collect()
+    };
+
+    search(start, goal, neighbors, cost, heuristic)
+}
+
+/// Finds the cheapest path between two triangles through their shared edges, with Dijkstra's
+/// algorithm. Returns `None` if the goal can't be reached.
+///
+/// `cost(from, to)` is the cost of stepping between two triangles sharing an edge, which must not be
+/// negative, or `None` if the step isn't allowed. For the shortest path on the sphere, use
+/// [`triangle_angle`]. If this icosphere is sparse, paths only go through generated triangles.
+pub fn triangle_dijkstra<T, S>(
+    ico: &S,
+    start: usize,
+    goal: usize,
+    cost: impl FnMut(usize, usize) -> Option<f32>,
+) -> Option<Path>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    triangle_astar(ico, start, goal, cost, |_| 0.0)
+}
+
+/// Same as [`triangle_dijkstra`], but with A*. See [`vertex_astar`] for the heuristic, with
+/// [`triangle_angle`] instead of [`vertex_angle`].
+pub fn triangle_astar<T, S>(
+    ico: &S,
+    start: usize,
+    goal: usize,
+    cost: impl FnMut(usize, usize) -> Option<f32>,
+    heuristic: impl FnMut(usize) -> f32,
+) -> Option<Path>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    let neighbors = |triangle_index: usize| {
+        adjacency::edge_neighbors(triangle_index, ico.binning_depth())
+            .into_iter()
+            .filter(|&neighbor| ico.contains_triangle(neighbor))
+            .collect()
+    };
+
+    search(start, goal, neighbors, cost, heuristic)
+}
+
+/// Vertices by the number of edges between them and `start`, up to `hops` edges away. The ring at
+/// index `i` holds every vertex exactly `i` edges away, so the first ring is `start` alone, and
+/// flattening the rings gives every vertex within `hops` edges.
+///
+/// Vertices in a ring are ordered by when the breadth-first search reached them.
+pub fn vertex_rings<T, S>(ico: &S, start: usize, hops: usize) -> Vec<Vec<usize>>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    let mut visited = HashSet::from([start]);
+    let mut rings = vec![vec![start]];
+
+    for _ in 0..hops {
+        let mut ring = Vec::new();
+
+        for vertex_index in rings.last().unwrap() {
+            for &neighbor in ico.neighbors().get(vertex_index).into_iter().flatten() {
+                if visited.insert(neighbor) {
+                    ring.push(neighbor);
+                }
+            }
+        }
+
+        if ring.is_empty() {
+            break;
+        }
+
+        rings.push(ring);
+    }
+
+    rings
+}
+
+/// The angle between two vertices, in radians.
+pub fn vertex_angle<T, S>(ico: &S, a: usize, b: usize) -> f32
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    let vertices = ico.vertices();
+    vertices[a].position().angle_between(vertices[b].position())
+}
+
+/// The angle between the centers of two triangles, in radians. The triangles must be generated.
+pub fn triangle_angle<T, S>(ico: &S, a: usize, b: usize) -> f32
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    let center = |triangle_index: usize| -> Vec3 {
+        ico.triangle(triangle_index)
+            .map(|vertex_index| ico.vertices()[vertex_index as usize].position().normalize())
+            .into_iter()
+            .sum()
+    };
+
+    center(a).angle_between(center(b))
+}
+
+/// A* over any graph of indices, where `neighbors(node)` lists the nodes one step away.
+fn search(
+    start: usize,
+    goal: usize,
+    mut neighbors: impl FnMut(usize) -> Vec<usize>,
+    mut cost: impl FnMut(usize, usize) -> Option<f32>,
+    mut heuristic: impl FnMut(usize) -> f32,
+) -> Option<Path> {
+    // The cheapest known cost to reach each node, and the node it's reached from
+    let mut costs = HashMap::from([(start, 0.0)]);
+    let mut previous = HashMap::new();
+
+    let mut open = BinaryHeap::from([OpenNode {
+        estimate: heuristic(start),
+        cost: 0.0,
+        index: start,
+    }]);
+
+    while let Some(node) = open.pop() {
+        if node.index == goal {
+            let mut indices = vec![goal];
+
+            while let Some(&index) = previous.get(indices.last().unwrap()) {
+                indices.push(index);
+            }
+
+            indices.reverse();
+
+            return Some(Path {
+                indices,
+                cost: node.cost,
+            });
+        }
+
+        // A cheaper way to this node was found after this entry was queued
+        if node.cost > costs[&node.index] {
+            continue;
+        }
+
+        for neighbor in neighbors(node.index) {
+            let Some(step_cost) = cost(node.index, neighbor) else {
+                continue;
+            };
+
+            let neighbor_cost = node.cost + step_cost;
+
+            if costs
+                .get(&neighbor)
+                .is_some_and(|&known_cost| known_cost <= neighbor_cost)
+            {
+                continue;
+            }
+
+            costs.insert(neighbor, neighbor_cost);
+            previous.insert(neighbor, node.index);
+
+            open.push(OpenNode {
+                estimate: neighbor_cost + heuristic(neighbor),
+                cost: neighbor_cost,
+                index: neighbor,
+            });
+        }
+    }
+
+    None
+}
+
+/// A node waiting to be explored, ordered so that [`BinaryHeap`] pops the lowest estimate first.
+struct OpenNode {
+    /// The cost to reach the node plus the heuristic.
+    estimate: f32,
+    cost: f32,
+    index: usize,
+}
+
+impl PartialEq for OpenNode {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for OpenNode {}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other
+            .estimate
+            .total_cmp(&self.estimate)
+            .then(other.index.cmp(&self.index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere, midpoint::MidpointRule, triangle_count};
+
+    /// Checks that every step of the path is between neighbors and that the costs add up.
+    fn assert_valid_path(
+        path: &Path,
+        start: usize,
+        goal: usize,
+        are_neighbors: impl Fn(usize, usize) -> bool,
+        cost: impl Fn(usize, usize) -> f32,
+    ) {
+        assert_eq!(path.indices.first(), Some(&start));
+        assert_eq!(path.indices.last(), Some(&goal));
+
+        let mut total = 0.0;
+
+        for step in path.indices.windows(2) {
+            assert!(are_neighbors(step[0], step[1]));
+            total += cost(step[0], step[1]);
+        }
+
+        assert!((total - path.cost).abs() < 1e-4);
+    }
+
+    #[test]
+    fn vertex_dijkstra_and_astar_agree() {
+        let ico = StaticIcosphere::<Vec3>::nth(3);
+        let cost = |a, b| vertex_angle(&ico, a, b);
+
+        for (start, goal) in [(0, 11), (3, 400), (150, 151), (600, 17), (42, 42)] {
+            let dijkstra = vertex_dijkstra(&ico, start, goal, |a, b| Some(cost(a, b))).unwrap();
+            let astar = vertex_astar(
+                &ico,
+                start,
+                goal,
+                |a, b| Some(cost(a, b)),
+                |vertex| cost(vertex, goal),
+            )
+            .unwrap();
+
+            for path in [&dijkstra, &astar] {
+                assert_valid_path(
+                    path,
+                    start,
+                    goal,
+                    |a, b| ico.neighbors[&a].contains(&b),
+                    cost,
+                );
+            }
+
+            assert!((dijkstra.cost - astar.cost).abs() < 1e-4);
+
+            // Every path along the edges is at least as long as the great circle arc
+            assert!(dijkstra.cost >= cost(start, goal) - 1e-4);
+        }
+    }
+
+    #[test]
+    fn triangle_dijkstra_and_astar_agree() {
+        let ico = StaticIcosphere::<Vec3>::nth(3);
+        let cost = |a, b| triangle_angle(&ico, a, b);
+
+        for (start, goal) in [(0, 1279), (17, 640), (300, 301), (5, 5)] {
+            let dijkstra = triangle_dijkstra(&ico, start, goal, |a, b| Some(cost(a, b))).unwrap();
+            let astar = triangle_astar(
+                &ico,
+                start,
+                goal,
+                |a, b| Some(cost(a, b)),
+                |triangle| cost(triangle, goal),
+            )
+            .unwrap();
+
+            for path in [&dijkstra, &astar] {
+                assert_valid_path(
+                    path,
+                    start,
+                    goal,
+                    |a, b| adjacency::edge_neighbors(a, 3).contains(&b),
+                    cost,
+                );
+            }
+
+            assert!((dijkstra.cost - astar.cost).abs() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn blocked_steps_are_avoided() {
+        let ico = StaticIcosphere::<Vec3>::nth(2);
+        let (start, goal) = (0, 3);
+
+        let direct =
+            vertex_dijkstra(&ico, start, goal, |a, b| Some(vertex_angle(&ico, a, b))).unwrap();
+        let detour_vertex = direct.indices[1];
+
+        let detour = vertex_dijkstra(&ico, start, goal, |a, b| {
+            (b != detour_vertex).then(|| vertex_angle(&ico, a, b))
+        })
+        .unwrap();
+
+        assert!(!detour.indices.contains(&detour_vertex));
+        assert!(detour.cost >= direct.cost);
+
+        // Walling off the goal leaves no path
+        let walled_off = vertex_dijkstra(&ico, start, goal, |a, b| {
+            (b != goal).then(|| vertex_angle(&ico, a, b))
+        });
+        assert_eq!(walled_off, None);
+
+        let walled_off = triangle_astar(
+            &ico,
+            0,
+            100,
+            |a, b| (a != 0).then(|| triangle_angle(&ico, a, b)),
+            |triangle| triangle_angle(&ico, triangle, 100),
+        );
+        assert_eq!(walled_off, None);
+    }
+
+    #[test]
+    fn rings_of_static_icosphere() {
+        let ico = StaticIcosphere::<Vec3>::nth(4);
+        let ring_sizes = |start| -> Vec<usize> {
+            vertex_rings(&ico, start, 4)
+                .iter()
+                .map(|ring| ring.len())
+                .collect()
+        };
+
+        // The vertices of the regular icosahedron have five neighbors
+        assert_eq!(ring_sizes(0), [1, 5, 10, 15, 20]);
+
+        // Far from those, every vertex has six
+        let face_center: Vec3 = crate::locate::triangle_corners(0, 0, MidpointRule::Normalized)
+            .into_iter()
+            .sum();
+        let (center_vertex, _) = ico.nearest_vertex(face_center).unwrap();
+        assert_eq!(ring_sizes(center_vertex), [1, 6, 12, 18, 24]);
+
+        let rings = vertex_rings(&ico, center_vertex, 4);
+        let flattened: HashSet<usize> = rings.iter().flatten().copied().collect();
+        assert_eq!(flattened.len(), 1 + 6 + 12 + 18 + 24);
+
+        // Each ring only touches the ones next to it
+        for (i, ring) in rings.iter().enumerate().skip(1) {
+            for vertex_index in ring {
+                assert!(
+                    ico.neighbors[vertex_index]
+                        .iter()
+                        .any(|neighbor| rings[i - 1].contains(neighbor))
+                );
+            }
+        }
+
+        // The rings stop when every vertex is reached
+        assert_eq!(
+            vertex_rings(&StaticIcosphere::<Vec3>::nth(0), 0, 10)
+                .iter()
+                .map(|ring| ring.len())
+                .collect::<Vec<_>>(),
+            [1, 5, 5, 1]
+        );
+    }
+
+    #[test]
+    fn paths_on_sparse_icosphere_stay_on_generated_triangles() {
+        let previous = SparseIcosphere::<Vec3>::filled(2);
+        let mut ico = SparseIcosphere::empty(3);
+
+        // Two faces of the regular icosahedron and a far away chunk
+        for parent_index in (0..32).chain([200]) {
+            ico.subdivide_chunk(&previous, parent_index);
+        }
+
+        let generated = ico.allocated_triangle_indices();
+        let (start, goal) = (generated[0], generated[127]);
+
+        let path =
+            triangle_dijkstra(&ico, start, goal, |a, b| Some(triangle_angle(&ico, a, b))).unwrap();
+        assert!(path.indices.iter().all(|&t| ico.contains_triangle(t)));
+
+        // The far away chunk and triangles that aren't generated can't be reached
+        let unreachable = [200 * 4, triangle_count(3) - 1];
+
+        for goal in unreachable {
+            let path =
+                triangle_dijkstra(&ico, start, goal, |a, b| Some(triangle_angle(&ico, a, b)));
+            assert_eq!(path, None);
+        }
+
+        let [a, _, _] = ico.triangle(start);
+        let [b, _, _] = ico.triangle(goal);
+        let path = vertex_dijkstra(&ico, a as usize, b as usize, |a, b| {
+            Some(vertex_angle(&ico, a, b))
+        })
+        .unwrap();
+        assert!(path.indices.iter().all(|v| ico.neighbors.contains_key(v)));
+    }
+}
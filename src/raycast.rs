@@ -0,0 +1,230 @@
+use glam::Vec3;
+
+use crate::{locate, midpoint::MidpointRule};
+
+/// The maximum number of triangles visited while searching for the one hit by a ray. The first
+/// guess is almost always correct, so this is only reached by rays that graze the silhouette.
+const MAX_RAYCAST_STEPS: usize = 16;
+
+/// Where a ray hit the surface of an icosphere.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RayHit {
+    /// Index of the triangle that was hit.
+    pub triangle_index: usize,
+
+    /// Distance along the ray to the hit point, in multiples of the ray direction's length.
+    pub distance: f32,
+
+    /// The hit point, on the flat triangle rather than on the unit sphere.
+    pub position: Vec3,
+
+    /// Weights of the triangle's three vertices (in the same order as [`crate::Icosphere::triangle`])
+    /// that add up to [`Self::position`]. The components sum to one.
+    pub barycentric: Vec3,
+}
+
+/// Casts a ray against the surface of an icosphere of the given binning depth, without needing the
+/// icosphere to exist.
+///
+/// Rays starting outside the icosphere hit the side facing them, and rays starting inside hit the
+/// inner side of the surface. Returns `None` if the ray misses.
+///
+/// Rather than testing every triangle, the point where the ray crosses the unit sphere is located in
+/// the triangle hierarchy (see [`locate::locate_triangle`]), and the search steps to the triangle under
+/// the intersection with that triangle's plane until the two agree.
+pub fn raycast(origin: Vec3, direction: Vec3, binning_depth: usize) -> Option<RayHit> {
+    raycast_with(origin, direction, binning_depth, MidpointRule::Normalized)
+}
+
+/// Same as [`raycast`], for an icosphere whose vertices were placed with the given rule.
+pub fn raycast_with(
+    origin: Vec3,
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> Option<RayHit> {
+    let (guess, _) = initial_guesses(origin, direction, binning_depth, midpoint_rule)?;
+
+    search(origin, direction, guess, binning_depth, midpoint_rule)
+}
+
+/// Every hit of a ray against the surface of an icosphere of the given binning depth, sorted by
+/// distance.
+///
+/// The surface is convex, so a ray starting outside it hits it at most twice, first where it enters
+/// and then on the inner side where it leaves, and a ray starting inside hits it once. The first hit
+/// is the one returned by [`raycast`].
+pub fn raycast_all(origin: Vec3, direction: Vec3, binning_depth: usize) -> Vec<RayHit> {
+    raycast_all_with(origin, direction, binning_depth, MidpointRule::Normalized)
+}
+
+/// Same as [`raycast_all`], for an icosphere whose vertices were placed with the given rule.
+pub fn raycast_all_with(
+    origin: Vec3,
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> Vec<RayHit> {
+    let Some((guess, exit_guess)) =
+        initial_guesses(origin, direction, binning_depth, midpoint_rule)
+    else {
+        return Vec::new();
+    };
+
+    let mut hits: Vec<RayHit> = [Some(guess), exit_guess]
+        .into_iter()
+        .flatten()
+        .filter_map(|guess| search(origin, direction, guess, binning_depth, midpoint_rule))
+        .collect();
+
+    // A ray grazing a single triangle finds it from both sides
+    hits.dedup_by_key(|hit| hit.triangle_index);
+    hits
+}
+
+/// Points on the unit sphere close to where the ray first hits the mesh, and where it leaves it
+/// again if it starts outside. Returns `None` if the ray misses the sphere.
+fn initial_guesses(
+    origin: Vec3,
+    direction: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> Option<(Vec3, Option<Vec3>)> {
+    // Every vertex is on the unit sphere, so a ray that misses the sphere misses the mesh too
+    let b = origin.dot(direction);
+    let a = direction.length_squared();
+    let c = origin.length_squared() - 1.0;
+    let discriminant = b * b - a * c;
+
+    if a == 0.0 || discriminant < 0.0 {
+        return None;
+    }
+
+    let exit = origin + direction * ((-b + discriminant.sqrt()) / a);
+
+    if c > 0.0 {
+        // Outside the sphere, the mesh can only be hit when it is in front of the ray
+        if b > 0.0 {
+            return None;
+        }
+
+        Some((
+            origin + direction * ((-b - discriminant.sqrt()) / a),
+            Some(exit),
+        ))
+    } else if is_outside_mesh(origin, binning_depth, midpoint_rule) {
+        // Between the mesh and the sphere, the hit is close to the origin
+        Some((origin, Some(exit)))
+    } else {
+        Some((exit, None))
+    }
+}
+
+/// Steps from the triangle under `guess` to the triangle under the intersection with its plane until
+/// the two agree.
+fn search(
+    origin: Vec3,
+    direction: Vec3,
+    mut guess: Vec3,
+    binning_depth: usize,
+    midpoint_rule: MidpointRule,
+) -> Option<RayHit> {
+    for _ in 0..MAX_RAYCAST_STEPS {
+        let (triangle_index, [p0, p1, p2]) =
+            locate::locate_triangle_corners(guess, binning_depth, midpoint_rule);
+
+        let normal = (p1 - p0).cross(p2 - p0);
+        let denominator = normal.dot(direction);
+
+        if denominator == 0.0 {
+            return None;
+        }
+
+        let distance = normal.dot(p0 - origin) / denominator;
+
+        if distance < 0.0 {
+            return None;
+        }
+
+        let position = origin + direction * distance;
+        let barycentric = barycentric(position, [p0, p1, p2]);
+
+        if barycentric.min_element() >= -1e-5 {
+            return Some(RayHit {
+                triangle_index,
+                distance,
+                position,
+                barycentric,
+            });
+        }
+
+        guess = position;
+    }
+
+    None
+}
+
+/// Whether `position` is in front of the triangle below it. Positions at the origin are never outside.
+fn is_outside_mesh(position: Vec3, binning_depth: usize, midpoint_rule: MidpointRule) -> bool {
+    if position == Vec3::ZERO {
+        return false;
+    }
+
+    let (_, [p0, p1, p2]) = locate::locate_triangle_corners(position, binning_depth, midpoint_rule);
+    let normal = (p1 - p0).cross(p2 - p0);
+
+    normal.dot(position - p0) > 0.0
+}
+
+/// Barycentric coordinates of `position` projected onto the plane of the triangle.
+pub(crate) fn barycentric(position: Vec3, [a, b, c]: [Vec3; 3]) -> Vec3 {
+    let ab = b - a;
+    let ac = c - a;
+    let ap = position - a;
+
+    let d00 = ab.dot(ab);
+    let d01 = ab.dot(ac);
+    let d11 = ac.dot(ac);
+    let d20 = ap.dot(ab);
+    let d21 = ap.dot(ac);
+
+    let denominator = d00 * d11 - d01 * d01;
+
+    let v = (d11 * d20 - d01 * d21) / denominator;
+    let w = (d00 * d21 - d01 * d20) / denominator;
+
+    Vec3::new(1.0 - v - w, v, w)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Icosphere, SparseIcosphere};
+
+    #[test]
+    fn ray_through_sparse_icosphere_hits_generated_back_side() {
+        let direction = Vec3::new(0.3, 0.5, 0.8).normalize();
+        let (origin, ray) = (direction * 5.0, -direction);
+
+        let regular = SparseIcosphere::<Vec3>::regular();
+        let mut ico = SparseIcosphere::empty(1);
+        ico.subdivide_chunk(&regular, regular.locate_triangle(-direction));
+
+        let hits = raycast_all(origin, ray, 1);
+        assert_eq!(hits.len(), 2);
+        assert!(hits[0].distance < hits[1].distance);
+        assert!(!ico.contains_triangle(hits[0].triangle_index));
+
+        let hit = ico.raycast(origin, ray).unwrap();
+        assert_eq!(hit, hits[1]);
+        assert_eq!(hit.triangle_index, ico.locate_triangle(-direction));
+    }
+
+    #[test]
+    fn ray_from_inside_hits_once() {
+        let hits = raycast_all(Vec3::ZERO, Vec3::X, 3);
+
+        assert_eq!(hits.len(), 1);
+        assert_eq!(Some(hits[0]), raycast(Vec3::ZERO, Vec3::X, 3));
+    }
+}
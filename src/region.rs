@@ -0,0 +1,407 @@
+use std::{
+    f32::consts::{FRAC_PI_2, PI, TAU},
+    ops::Range,
+};
+
+use glam::Vec3;
+
+use crate::{
+    ICOSAHEDRON_TRIANGLES, culling::BOUNDS_MARGIN, icosahedron_positions, locate,
+    midpoint::MidpointRule, triangle_id::TriangleId,
+};
+
+/// A part of the sphere to find the triangles of, with [`Region::cover`].
+///
+/// Latitude and longitude are in radians and follow [`crate::attributes::equirectangular_uv`]:
+/// latitude increases towards +y, and longitude is `z.atan2(x)`, so it's zero towards +x and
+/// increases eastwards, towards +z.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Region {
+    /// Every point within `angle` radians of the unit vector `center`.
+    Cap { center: Vec3, angle: f32 },
+
+    /// Every point with a latitude in `min_latitude..=max_latitude` and a longitude going eastwards
+    /// from `min_longitude` to `max_longitude`. If `max_longitude` is less than `min_longitude`, the
+    /// box crosses the antimeridian, and if it's a full turn or more past it, the box goes all the
+    /// way around.
+    LatLonBox {
+        min_latitude: f32,
+        max_latitude: f32,
+        min_longitude: f32,
+        max_longitude: f32,
+    },
+
+    /// A convex polygon whose edges are great circle arcs between unit vectors, wound
+    /// counter-clockwise when viewed from outside. It must fit in a hemisphere.
+    Polygon(Vec<Vec3>),
+}
+
+/// The triangles of an icosphere that intersect a [`Region`], at mixed binning depths: triangles
+/// completely inside the region are kept whole instead of being split into their descendants.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RegionCover {
+    /// The binning depth the region was covered at. No triangle is deeper than this.
+    pub binning_depth: usize,
+
+    /// The covering triangles, in ascending order. None of them contains another.
+    pub triangles: Vec<TriangleId>,
+}
+
+impl RegionCover {
+    /// Ranges of triangle indices at [`Self::binning_depth`] covered by the triangles, merged where
+    /// they touch.
+    pub fn triangle_ranges(&self) -> Vec<Range<usize>> {
+        let mut ranges: Vec<Range<usize>> = Vec::new();
+
+        for triangle in &self.triangles {
+            let generations = 2 * (self.binning_depth - triangle.binning_depth());
+            let range = (triangle.triangle_index() << generations)
+                ..((triangle.triangle_index() + 1) << generations);
+
+            match ranges.last_mut() {
+                Some(last) if last.end == range.start => last.end = range.end,
+                _ => ranges.push(range),
+            }
+        }
+
+        ranges
+    }
+
+    /// Every covered triangle index at [`Self::binning_depth`], in ascending order.
+    pub fn triangle_indices(&self) -> impl Iterator<Item = usize> {
+        self.triangle_ranges().into_iter().flatten()
+    }
+
+    /// The number of covered triangles at [`Self::binning_depth`].
+    pub fn triangle_count(&self) -> usize {
+        self.triangle_ranges().iter().map(|range| range.len()).sum()
+    }
+}
+
+impl Region {
+    /// Finds the triangles that intersect this region in an icosphere of the given binning depth.
+    ///
+    /// Like [`locate::locate_triangle`], this doesn't need an icosphere to exist: starting from the
+    /// faces of the regular icosahedron, triangles completely inside or outside the region are
+    /// kept or dropped whole, and only the ones crossing its boundary are split into their children,
+    /// so the work grows with the length of the boundary rather than the area.
+    ///
+    /// The cover is conservative. Every triangle intersecting the region is included, but for a
+    /// [`Self::LatLonBox`] or a [`Self::Polygon`], triangles near a corner of the region that
+    /// don't quite reach it may be included too.
+    pub fn cover(&self, binning_depth: usize) -> RegionCover {
+        self.cover_with(binning_depth, MidpointRule::Normalized)
+    }
+
+    /// Same as [`Self::cover`], for an icosphere whose vertices were placed with the given rule.
+    ///
+    /// With rules other than [`MidpointRule::Normalized`], the children of a triangle can bulge past
+    /// its edges, so triangles are tested with a wide margin and more of the ones near the boundary
+    /// are included.
+    pub fn cover_with(&self, binning_depth: usize, midpoint_rule: MidpointRule) -> RegionCover {
+        let parts = self.convex_parts();
+        let positions = icosahedron_positions();
+
+        let mut triangles = Vec::new();
+        let mut stack: Vec<(TriangleId, [Vec3; 3])> = ICOSAHEDRON_TRIANGLES
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(face, triangle)| {
+                (
+                    TriangleId::new(0, face),
+                    triangle.map(|i| positions[i as usize]),
+                )
+            })
+            .collect();
+
+        while let Some((triangle, corners)) = stack.pop() {
+            // Only normalized midpoints keep the children within the parent's great circle edges, so
+            // with other rules, a larger triangle containing all of them is tested instead
+            let bounds = if midpoint_rule == MidpointRule::Normalized {
+                corners
+            } else {
+                inflate(corners)
+            };
+
+            // The region is the union of its parts
+            let overlaps = parts.iter().map(|part| part.overlap(bounds));
+
+            let overlap = overlaps.fold(Overlap::Outside, |overlap, part_overlap| {
+                overlap.max(part_overlap)
+            });
+
+            match overlap {
+                Overlap::Outside => {}
+                Overlap::Inside => triangles.push(triangle),
+                Overlap::Partial if triangle.binning_depth() == binning_depth => {
+                    triangles.push(triangle)
+                }
+                Overlap::Partial => {
+                    for (child, child_triangle) in triangle.children().into_iter().enumerate().rev()
+                    {
+                        let child_corners = locate::child_corners(corners, child, midpoint_rule);
+                        stack.push((child_triangle, child_corners));
+                    }
+                }
+            }
+        }
+
+        RegionCover {
+            binning_depth,
+            triangles,
+        }
+    }
+
+    /// Splits the region into convex parts, whose union is the region.
+    fn convex_parts(&self) -> Vec<ConvexPart> {
+        match self {
+            Region::Cap { center, angle } => vec![ConvexPart {
+                caps: vec![(center.normalize(), *angle)],
+                corners: Vec::new(),
+            }],
+            Region::LatLonBox {
+                min_latitude,
+                max_latitude,
+                min_longitude,
+                max_longitude,
+            } => {
+                let latitude_caps = [
+                    (Vec3::Y, FRAC_PI_2 - min_latitude),
+                    (Vec3::NEG_Y, FRAC_PI_2 + max_latitude),
+                ];
+
+                let width = if max_longitude - min_longitude >= TAU {
+                    TAU
+                } else {
+                    (max_longitude - min_longitude).rem_euclid(TAU)
+                };
+
+                // Lunes of up to half the sphere are the intersection of two hemispheres, so wider
+                // ones are split in two
+                let lunes = if width >= TAU {
+                    vec![None]
+                } else if width > PI {
+                    let middle = min_longitude + width / 2.0;
+                    vec![
+                        Some((*min_longitude, middle)),
+                        Some((middle, *max_longitude)),
+                    ]
+                } else {
+                    vec![Some((*min_longitude, *max_longitude))]
+                };
+
+                lunes
+                    .into_iter()
+                    .map(|lune| {
+                        let mut caps = latitude_caps.to_vec();
+
+                        if let Some((from, to)) = lune {
+                            // Points east of `from` and west of `to`, within half a turn
+                            caps.push((Vec3::new(-from.sin(), 0.0, from.cos()), FRAC_PI_2));
+                            caps.push((Vec3::new(to.sin(), 0.0, -to.cos()), FRAC_PI_2));
+                        }
+
+                        ConvexPart {
+                            caps,
+                            corners: Vec::new(),
+                        }
+                    })
+                    .collect()
+            }
+            Region::Polygon(vertices) => {
+                let caps = (0..vertices.len())
+                    .map(|i| {
+                        let (from, to) = (vertices[i], vertices[(i + 1) % vertices.len()]);
+                        (from.cross(to).normalize(), FRAC_PI_2)
+                    })
+                    .collect();
+
+                vec![ConvexPart {
+                    caps,
+                    corners: vertices.clone(),
+                }]
+            }
+        }
+    }
+}
+
+/// How a triangle overlaps a region, ordered from least to most.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Overlap {
+    Outside,
+    Partial,
+    Inside,
+}
+
+/// The intersection of a set of caps, as `(center, angle)`.
+struct ConvexPart {
+    caps: Vec<(Vec3, f32)>,
+
+    /// Corners of the part, if its edges are great circle arcs between them.
+    corners: Vec<Vec3>,
+}
+
+impl ConvexPart {
+    fn overlap(&self, triangle: [Vec3; 3]) -> Overlap {
+        let mut overlap = Overlap::Inside;
+
+        for &(center, angle) in &self.caps {
+            if !cap_intersects(center, angle, triangle) {
+                return Overlap::Outside;
+            }
+
+            // The rest of the sphere is the cap around the opposite point
+            if cap_intersects(-center, PI - angle, triangle) {
+                overlap = Overlap::Partial;
+            }
+        }
+
+        // Two convex polygons are apart if an edge of either one separates them. The caps already
+        // test the part's own edges.
+        let [a, b, c] = triangle;
+        let separated = !self.corners.is_empty()
+            && [(a, b), (b, c), (c, a)].into_iter().any(|(from, to)| {
+                let normal = from.cross(to);
+                self.corners.iter().all(|corner| corner.dot(normal) < 0.0)
+            });
+
+        if separated { Overlap::Outside } else { overlap }
+    }
+}
+
+/// A triangle containing every descendant of the given one, whatever the midpoint rule.
+///
+/// The descendants stay within the cap around the triangle's center that reaches its farthest
+/// corner. Doubling the distances from the center gives a triangle whose inscribed circle is about
+/// that cap, so this moves the corners a bit farther, like [`crate::culling::ChunkBounds`] does.
+fn inflate(corners: [Vec3; 3]) -> [Vec3; 3] {
+    let center = (corners[0] + corners[1] + corners[2]).normalize();
+    corners.map(|corner| (center + (corner - center) * 2.0 * BOUNDS_MARGIN).normalize())
+}
+
+/// Whether any point of the spherical triangle is within `angle` of `center`.
+fn cap_intersects(center: Vec3, angle: f32, [a, b, c]: [Vec3; 3]) -> bool {
+    if angle < 0.0 {
+        return false;
+    }
+
+    let edges = [(a, b), (b, c), (c, a)];
+
+    edges
+        .iter()
+        .all(|&(from, to)| center.dot(from.cross(to)) >= 0.0)
+        || edges
+            .iter()
+            .any(|&(from, to)| arc_distance(center, from, to) <= angle)
+}
+
+/// The angle between `point` and the closest point of the great circle arc from `from` to `to`.
+fn arc_distance(point: Vec3, from: Vec3, to: Vec3) -> f32 {
+    let normal = from.cross(to).normalize_or_zero();
+    let projected = point - normal * point.dot(normal);
+
+    // The closest point on the whole great circle is on the arc if it's between the arc's ends
+    let on_arc = normal != Vec3::ZERO
+        && projected != Vec3::ZERO
+        && from.cross(projected).dot(normal) >= 0.0
+        && projected.cross(to).dot(normal) >= 0.0;
+
+    if on_arc {
+        point.dot(normal).abs().min(1.0).asin()
+    } else {
+        point.angle_between(from).min(point.angle_between(to))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BINNING_DEPTH: usize = 4;
+
+    /// Caps of various sizes all over the sphere, from a xorshift generator.
+    fn random_caps(count: usize) -> Vec<(Vec3, f32)> {
+        let mut state = 0x2545_f491_u32;
+        let mut random = move || {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as f32 / u32::MAX as f32
+        };
+
+        (0..count)
+            .map(|_| {
+                let center = Vec3::new(random(), random(), random()) * 2.0 - 1.0;
+                (center.normalize(), random() * random() * 0.5)
+            })
+            .collect()
+    }
+
+    /// Every triangle with a corner inside the cap, found by testing all of them.
+    fn triangles_with_corner_in_cap(
+        corners: &[[Vec3; 3]],
+        center: Vec3,
+        angle: f32,
+    ) -> impl Iterator<Item = usize> {
+        (0..corners.len()).filter(move |&triangle_index| {
+            corners[triangle_index]
+                .iter()
+                .any(|corner| corner.angle_between(center) < angle)
+        })
+    }
+
+    #[test]
+    fn cap_cover_contains_every_triangle_reaching_into_the_cap() {
+        for midpoint_rule in [
+            MidpointRule::Normalized,
+            MidpointRule::Slerp,
+            MidpointRule::EqualArea,
+        ] {
+            let corners: Vec<[Vec3; 3]> = (0..crate::triangle_count(BINNING_DEPTH))
+                .map(|triangle_index| {
+                    locate::triangle_corners(triangle_index, BINNING_DEPTH, midpoint_rule)
+                })
+                .collect();
+
+            for (center, angle) in random_caps(400) {
+                let cover = Region::Cap { center, angle }.cover_with(BINNING_DEPTH, midpoint_rule);
+                let covered: Vec<usize> = cover.triangle_indices().collect();
+
+                for triangle_index in triangles_with_corner_in_cap(&corners, center, angle) {
+                    assert!(
+                        covered.binary_search(&triangle_index).is_ok(),
+                        "{midpoint_rule:?} cover of cap around {center} with angle {angle} misses \
+                         triangle {triangle_index}"
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn equal_area_cover_includes_triangles_bulging_past_their_parent() {
+        let region = Region::Cap {
+            center: Vec3::new(-0.2161, -0.9486, 0.2311).normalize(),
+            angle: 0.0435,
+        };
+
+        let cover = region.cover_with(BINNING_DEPTH, MidpointRule::EqualArea);
+        let bulging: TriangleId = "L2330".parse().unwrap();
+
+        assert!(cover.triangles.contains(&bulging));
+    }
+
+    #[test]
+    fn cover_uses_normalized_midpoints() {
+        let region = Region::Cap {
+            center: Vec3::new(0.2, 0.9, -0.4).normalize(),
+            angle: 0.3,
+        };
+
+        assert_eq!(
+            region.cover(BINNING_DEPTH),
+            region.cover_with(BINNING_DEPTH, MidpointRule::Normalized)
+        );
+    }
+}
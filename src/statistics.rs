@@ -0,0 +1,171 @@
+use glam::DVec3;
+
+use crate::{Icosphere, IcosphereVertex};
+
+/// Area and edge length statistics of the generated triangles of an icosphere, measured on the unit
+/// sphere: areas are solid angles in steradians and edge lengths are great circle distances in radians.
+///
+/// Useful for comparing how uniform different [`crate::midpoint::MidpointRule`]s are.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MeshStatistics {
+    pub triangle_count: usize,
+
+    pub min_area: f64,
+    pub max_area: f64,
+    pub mean_area: f64,
+
+    /// Standard deviation of the triangle areas.
+    pub area_deviation: f64,
+
+    pub min_edge_length: f64,
+    pub max_edge_length: f64,
+    pub mean_edge_length: f64,
+}
+
+impl MeshStatistics {
+    /// Measures every generated triangle. Edges shared by two triangles are counted twice.
+    pub fn new<T: IcosphereVertex, S: Icosphere<T>>(ico: &S) -> Self {
+        let vertices = ico.vertices();
+
+        let mut areas = Vec::with_capacity(ico.allocated_triangle_count());
+        let mut edge_lengths = Vec::with_capacity(ico.allocated_triangle_count() * 3);
+
+        for triangle_index in ico.allocated_triangle_indices() {
+            let corners = ico
+                .triangle(triangle_index)
+                .map(|i| vertices[i as usize].position().as_dvec3().normalize());
+
+            areas.push(spherical_triangle_area(corners));
+
+            let [a, b, c] = corners;
+            for (i, j) in [(a, b), (b, c), (c, a)] {
+                edge_lengths.push(i.angle_between(j));
+            }
+        }
+
+        let (min_area, max_area, mean_area) = min_max_mean(&areas);
+        let (min_edge_length, max_edge_length, mean_edge_length) = min_max_mean(&edge_lengths);
+
+        let area_deviation = (areas
+            .iter()
+            .map(|area| (area - mean_area).powi(2))
+            .sum::<f64>()
+            / areas.len().max(1) as f64)
+            .sqrt();
+
+        Self {
+            triangle_count: areas.len(),
+            min_area,
+            max_area,
+            mean_area,
+            area_deviation,
+            min_edge_length,
+            max_edge_length,
+            mean_edge_length,
+        }
+    }
+
+    /// Ratio between the largest and smallest triangle area. One means every triangle has the same area.
+    pub fn area_ratio(&self) -> f64 {
+        self.max_area / self.min_area
+    }
+
+    /// Ratio between the longest and shortest edge. One means every edge has the same length.
+    pub fn edge_length_ratio(&self) -> f64 {
+        self.max_edge_length / self.min_edge_length
+    }
+}
+
+/// Solid angle of the spherical triangle between three unit vectors.
+pub(crate) fn spherical_triangle_area([a, b, c]: [DVec3; 3]) -> f64 {
+    // Van Oosterom and Strackee's formula
+    let numerator = a.dot(b.cross(c)).abs();
+    let denominator = 1.0 + a.dot(b) + b.dot(c) + c.dot(a);
+
+    2.0 * numerator.atan2(denominator)
+}
+
+fn min_max_mean(values: &[f64]) -> (f64, f64, f64) {
+    if values.is_empty() {
+        return (0.0, 0.0, 0.0);
+    }
+
+    let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+    let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let mean = values.iter().sum::<f64>() / values.len() as f64;
+
+    (min, max, mean)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f64::consts::PI;
+
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{StaticIcosphere, midpoint::MidpointRule};
+
+    #[test]
+    fn octant_area() {
+        assert!((spherical_triangle_area([DVec3::X, DVec3::Y, DVec3::Z]) - PI / 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn regular_icosahedron_is_uniform() {
+        let statistics = MeshStatistics::new(&StaticIcosphere::<Vec3>::nth(0));
+
+        assert_eq!(statistics.triangle_count, 20);
+        assert!((statistics.mean_area - 4.0 * PI / 20.0).abs() < 1e-6);
+        assert!(statistics.area_deviation < 1e-6);
+        assert!((statistics.area_ratio() - 1.0).abs() < 1e-6);
+        assert!((statistics.edge_length_ratio() - 1.0).abs() < 1e-6);
+        assert!((statistics.mean_edge_length - 1.107_148_7).abs() < 1e-6);
+    }
+
+    #[test]
+    fn areas_add_up_to_the_sphere() {
+        for rule in [
+            MidpointRule::Normalized,
+            MidpointRule::Slerp,
+            MidpointRule::EqualArea,
+        ] {
+            let statistics = MeshStatistics::new(&StaticIcosphere::<Vec3>::nth_with(3, rule));
+
+            assert_eq!(statistics.triangle_count, 1280);
+            assert!((statistics.mean_area * 1280.0 - 4.0 * PI).abs() < 1e-4);
+            assert!(statistics.min_area <= statistics.mean_area);
+            assert!(statistics.mean_area <= statistics.max_area);
+            assert!(statistics.min_edge_length <= statistics.mean_edge_length);
+            assert!(statistics.mean_edge_length <= statistics.max_edge_length);
+        }
+    }
+
+    #[test]
+    fn equal_area_and_relaxation_even_out_areas() {
+        let normalized = StaticIcosphere::<Vec3>::nth(3);
+        let equal_area = StaticIcosphere::<Vec3>::nth_with(3, MidpointRule::EqualArea);
+        let mut relaxed = normalized.clone();
+        relaxed.relax_areas(20);
+
+        let deviation = |ico| MeshStatistics::new(ico).area_deviation;
+
+        assert!(deviation(&equal_area) < deviation(&normalized));
+        assert!(deviation(&relaxed) < deviation(&normalized) / 2.0);
+
+        let relaxed_statistics = MeshStatistics::new(&relaxed);
+        assert!((relaxed_statistics.mean_area * 1280.0 - 4.0 * PI).abs() < 1e-4);
+        assert!(relaxed_statistics.area_ratio() < MeshStatistics::new(&normalized).area_ratio());
+
+        // Only the vertices added by subdividing move, and they stay on the sphere
+        assert_eq!(relaxed.vertices[..12], normalized.vertices[..12]);
+        assert_ne!(relaxed.vertices[12..], normalized.vertices[12..]);
+        assert!(
+            relaxed
+                .vertices
+                .iter()
+                .all(|vertex| (vertex.length() - 1.0).abs() < 1e-6)
+        );
+        assert_eq!(relaxed.triangles, normalized.triangles);
+    }
+}
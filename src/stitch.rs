@@ -0,0 +1,275 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{Icosphere, IcosphereVertex, adjacency, levels::IcosphereLevels, locate};
+
+impl<T, S> IcosphereLevels<T, S>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T>,
+{
+    /// Splits chunks of a selection, such as the one from [`Self::select_chunks`], until every pair
+    /// of chunks sharing an edge is at most one level apart, which [`Self::stitched_chunk_indices`]
+    /// needs to remove every crack. The new chunks are generated with [`Self::update_chunk`] if they
+    /// aren't already.
+    ///
+    /// The selection must not contain overlapping chunks. Chunks are only ever split, so the result
+    /// covers the same area, and chunks that were already balanced keep their relative order.
+    pub fn balance_chunks(&mut self, selected: &[(usize, usize)]) -> Vec<(usize, usize)> {
+        let mut chunks: HashSet<(usize, usize)> = selected.iter().copied().collect();
+        let mut queue: Vec<(usize, usize)> = selected.to_vec();
+
+        while let Some(chunk) = queue.pop() {
+            if !chunks.contains(&chunk) {
+                continue;
+            }
+
+            let (level, chunk_index) = chunk;
+
+            for edge in 0..3 {
+                let Some(coarse) = self.coarser_neighbor(&chunks, level, chunk_index, edge) else {
+                    continue;
+                };
+
+                if coarse.0 + 1 >= level {
+                    continue;
+                }
+
+                chunks.remove(&coarse);
+
+                for subchunk in self.subchunks(coarse.0, coarse.1) {
+                    self.update_chunk(subchunk.0, subchunk.1);
+
+                    chunks.insert(subchunk);
+                    queue.push(subchunk);
+                }
+
+                // The subchunks may still be too coarse for this chunk
+                queue.push(chunk);
+            }
+        }
+
+        // Keep the original order where possible, and order new chunks by level and index
+        let mut balanced: Vec<(usize, usize)> = selected
+            .iter()
+            .copied()
+            .filter(|chunk| chunks.remove(chunk))
+            .collect();
+
+        let mut added: Vec<(usize, usize)> = chunks.into_iter().collect();
+        added.sort_unstable();
+        balanced.extend(added);
+
+        balanced
+    }
+
+    /// Same as [`Self::flattened_chunk_indices`] for the chunk at `level` and `chunk_index`, but the
+    /// vertices on edges shared with coarser chunks in `selected` are snapped to the vertices of the
+    /// coarser chunk, so there are no cracks between them. Triangles that collapse are left out.
+    ///
+    /// The indices still refer to the vertices of [`Self::get`]`(level)`, since every level has a
+    /// copy of the vertices of the levels below it. Chunks more than one level apart can still have
+    /// cracks between them, see [`Self::balance_chunks`].
+    pub fn stitched_chunk_indices(
+        &self,
+        selected: &[(usize, usize)],
+        level: usize,
+        chunk_index: usize,
+    ) -> Vec<u32> {
+        let chunks: HashSet<(usize, usize)> = selected.iter().copied().collect();
+
+        let ico = self.get(level);
+        let binning_depth = self.binning_depth_at_level(level);
+        let cover_binning_depth = self.cover_binning_depth(level);
+
+        let mut snapped: HashMap<u32, u32> = HashMap::new();
+
+        for edge in 0..3 {
+            let Some((coarse_level, _)) = self.coarser_neighbor(&chunks, level, chunk_index, edge)
+            else {
+                continue;
+            };
+
+            let coarse_binning_depth = self.binning_depth_at_level(coarse_level);
+            if coarse_binning_depth < cover_binning_depth {
+                continue;
+            }
+
+            // Walk the fine triangles along the edge in order. Edge `k` of a triangle is split into
+            // edge 0 of its child `k` and edge 2 of its child `(k + 1) % 3`.
+            let mut along_edge = vec![(chunk_index, edge)];
+
+            for _ in cover_binning_depth..binning_depth {
+                along_edge = along_edge
+                    .into_iter()
+                    .flat_map(|(triangle_index, k)| {
+                        [
+                            (triangle_index * 4 + k, 0),
+                            (triangle_index * 4 + (k + 1) % 3, 2),
+                        ]
+                    })
+                    .collect();
+            }
+
+            let mut edge_vertices: Vec<u32> = along_edge
+                .iter()
+                .map(|&(triangle_index, k)| ico.triangle(triangle_index)[k])
+                .collect();
+
+            let &(last_triangle, last_edge) = along_edge.last().unwrap();
+            edge_vertices.push(ico.triangle(last_triangle)[(last_edge + 1) % 3]);
+
+            // Every `spacing`th vertex is also a vertex of the coarser chunk
+            let spacing = 1 << (binning_depth - coarse_binning_depth);
+
+            for (i, &vertex) in edge_vertices.iter().enumerate() {
+                let nearest = (i + spacing / 2 - 1) / spacing * spacing;
+                snapped.insert(vertex, edge_vertices[nearest]);
+            }
+        }
+
+        self.chunk_triangles(level, chunk_index)
+            .map(|triangle_index| {
+                ico.triangle(triangle_index)
+                    .map(|vertex| snapped.get(&vertex).copied().unwrap_or(vertex))
+            })
+            .filter(|[a, b, c]| a != b && b != c && c != a)
+            .flatten()
+            .collect()
+    }
+
+    /// The chunk in `chunks` across edge `edge` of the triangle covering the given chunk, if it is
+    /// at a lower level. Edges are numbered like in [`adjacency::edge_neighbors`].
+    fn coarser_neighbor(
+        &self,
+        chunks: &HashSet<(usize, usize)>,
+        level: usize,
+        chunk_index: usize,
+        edge: usize,
+    ) -> Option<(usize, usize)> {
+        let cover_binning_depth = self.cover_binning_depth(level);
+        let (neighbor, _) = adjacency::edge_neighbor(chunk_index, edge, cover_binning_depth);
+
+        (0..level).rev().find_map(|coarse_level| {
+            let generations = cover_binning_depth - self.cover_binning_depth(coarse_level);
+            let coarse_index = locate::ancestor_index(neighbor, generations);
+
+            chunks
+                .contains(&(coarse_level, coarse_index))
+                .then_some((coarse_level, coarse_index))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ops::Range;
+
+    use glam::Vec3;
+
+    use super::*;
+    use crate::{SparseIcosphere, triangle_count};
+
+    type Levels = IcosphereLevels<Vec3, SparseIcosphere<Vec3>>;
+
+    /// Level 1 everywhere, except for the first chunk, which gets more detailed down to level 4 in
+    /// one corner.
+    fn unbalanced_selection(levels: &mut Levels) -> Vec<(usize, usize)> {
+        let mut selected: Vec<(usize, usize)> =
+            (1..levels.chunk_count(1)).map(|i| (1, i)).collect();
+        for level in 2..5 {
+            selected.extend((1..4).map(|i| (level, i)));
+        }
+        selected.push((4, 0));
+
+        for &(level, chunk_index) in &selected {
+            levels.update_chunk(level, chunk_index);
+        }
+
+        selected
+    }
+
+    /// Triangle indices at the deepest level covered by each chunk.
+    fn deepest_triangles(levels: &Levels, (level, chunk_index): (usize, usize)) -> Range<usize> {
+        let generations = levels.max_binning_depth - levels.binning_depth_at_level(level);
+        let triangles = levels.chunk_triangles(level, chunk_index);
+
+        (triangles.start << (2 * generations))..(triangles.end << (2 * generations))
+    }
+
+    /// Directed edges of every triangle, by the bits of their vertex positions.
+    fn edges(levels: &Levels, level: usize, indices: &[u32]) -> Vec<([u32; 3], [u32; 3])> {
+        let vertices = levels.get(level).vertices();
+        let key = |vertex_index: u32| vertices[vertex_index as usize].to_array().map(f32::to_bits);
+
+        indices
+            .chunks_exact(3)
+            .flat_map(|triangle| {
+                (0..3).map(move |k| (key(triangle[k]), key(triangle[(k + 1) % 3])))
+            })
+            .collect()
+    }
+
+    /// Directed edges without a matching edge in the opposite direction.
+    fn unmatched_edges(edges: &[([u32; 3], [u32; 3])]) -> usize {
+        let set: HashSet<_> = edges.iter().copied().collect();
+
+        edges
+            .iter()
+            .filter(|&&(a, b)| !set.contains(&(b, a)))
+            .count()
+    }
+
+    #[test]
+    fn balanced_chunks_partition_the_sphere() {
+        let mut levels = Levels::new(1, 5, 1);
+        let selected = unbalanced_selection(&mut levels);
+        let balanced = levels.balance_chunks(&selected);
+
+        let chunks: HashSet<(usize, usize)> = balanced.iter().copied().collect();
+        assert_eq!(chunks.len(), balanced.len());
+
+        for &(level, chunk_index) in &balanced {
+            for edge in 0..3 {
+                if let Some((coarse_level, _)) =
+                    levels.coarser_neighbor(&chunks, level, chunk_index, edge)
+                {
+                    assert!(coarse_level + 1 >= level);
+                }
+            }
+        }
+
+        let mut ranges: Vec<Range<usize>> = balanced
+            .iter()
+            .map(|&chunk| deepest_triangles(&levels, chunk))
+            .collect();
+        ranges.sort_unstable_by_key(|range| range.start);
+
+        let mut end = 0;
+        for range in ranges {
+            assert_eq!(range.start, end);
+            end = range.end;
+        }
+        assert_eq!(end, triangle_count(levels.max_binning_depth));
+    }
+
+    #[test]
+    fn stitched_chunks_have_no_cracks() {
+        let mut levels = Levels::new(1, 5, 1);
+        let selected = unbalanced_selection(&mut levels);
+        let balanced = levels.balance_chunks(&selected);
+
+        let mut stitched = Vec::new();
+        let mut unstitched = Vec::new();
+
+        for &(level, chunk_index) in &balanced {
+            let indices = levels.stitched_chunk_indices(&balanced, level, chunk_index);
+            stitched.extend(edges(&levels, level, &indices));
+
+            let indices = levels.flattened_chunk_indices(level, chunk_index);
+            unstitched.extend(edges(&levels, level, &indices));
+        }
+
+        assert!(unmatched_edges(&unstitched) > 0);
+        assert_eq!(unmatched_edges(&stitched), 0);
+    }
+}
@@ -0,0 +1,385 @@
+use std::{fmt, ops::RangeInclusive, str::FromStr};
+
+use glam::Vec3;
+
+use crate::{adjacency, locate, midpoint::MidpointRule, triangle_count};
+
+/// The deepest binning depth a [`TriangleId`] can address.
+pub const MAX_TRIANGLE_ID_DEPTH: usize = 29;
+
+/// Letters naming the 20 faces of the regular icosahedron in the string form of a [`TriangleId`].
+const FACE_LETTERS: &[u8; 20] = b"ABCDEFGHIJKLMNOPQRST";
+
+/// A triangle at any binning depth, packed into a `u64`, like the trixel IDs of the Hierarchical
+/// Triangular Mesh.
+///
+/// Triangle indices already form a quadtree, where the children of a triangle are at
+/// `parent_index * 4..parent_index * 4 + 4`, but an index alone doesn't say which depth it is at. An
+/// ID stores the index shifted to the top of the `u64`, followed by a single marker bit whose
+/// position encodes the depth:
+///
+/// ```text
+/// index << (2 * (MAX_TRIANGLE_ID_DEPTH - depth) + 1) | 1 << (2 * (MAX_TRIANGLE_ID_DEPTH - depth))
+/// ```
+///
+/// IDs are ordered along the quadtree, so the descendants of a triangle at every depth are exactly
+/// the IDs in [`Self::descendant_range`], which makes them work well as keys in sorted storage.
+///
+/// The string form is the letter of the face of the regular icosahedron (`A` to `T`), followed by
+/// which child (`0` to `3`) is taken at every depth, so `"C0123"` is at binning depth 4.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(try_from = "u64", into = "u64")
+)]
+pub struct TriangleId(u64);
+
+impl TriangleId {
+    /// The triangle at `triangle_index` in an icosphere of the given binning depth.
+    ///
+    /// Panics if the binning depth is larger than [`MAX_TRIANGLE_ID_DEPTH`] or the index is out of
+    /// range.
+    pub fn new(binning_depth: usize, triangle_index: usize) -> Self {
+        assert!(
+            binning_depth <= MAX_TRIANGLE_ID_DEPTH,
+            "Triangle IDs only go up to binning depth {MAX_TRIANGLE_ID_DEPTH}"
+        );
+        assert!(
+            triangle_index < triangle_count(binning_depth),
+            "Triangle index {triangle_index} is out of range at binning depth {binning_depth}"
+        );
+
+        let marker = Self::marker_bit(binning_depth);
+
+        Self(((triangle_index as u64) << 1 | 1) * marker)
+    }
+
+    /// The ID of the triangle containing `direction` at the given binning depth. See
+    /// [`locate::locate_triangle`].
+    pub fn locate(direction: Vec3, binning_depth: usize) -> Self {
+        Self::new(
+            binning_depth,
+            locate::locate_triangle(direction, binning_depth),
+        )
+    }
+
+    /// Reads an ID from its `u64` form, or returns `None` if it doesn't address a triangle.
+    pub fn from_u64(id: u64) -> Option<Self> {
+        if id == 0 || !(id.trailing_zeros() as usize).is_multiple_of(2) {
+            return None;
+        }
+
+        let binning_depth = MAX_TRIANGLE_ID_DEPTH.checked_sub(id.trailing_zeros() as usize / 2)?;
+        let triangle_index = id >> (id.trailing_zeros() + 1);
+
+        (triangle_index < triangle_count(binning_depth) as u64).then_some(Self(id))
+    }
+
+    /// The `u64` form of this ID.
+    pub fn to_u64(self) -> u64 {
+        self.0
+    }
+
+    pub fn binning_depth(self) -> usize {
+        MAX_TRIANGLE_ID_DEPTH - self.0.trailing_zeros() as usize / 2
+    }
+
+    /// Index of the triangle in an icosphere of [`Self::binning_depth`].
+    pub fn triangle_index(self) -> usize {
+        (self.0 >> (self.0.trailing_zeros() + 1)) as usize
+    }
+
+    /// Index of the face of the regular icosahedron that contains the triangle.
+    pub fn face(self) -> usize {
+        self.ancestor(0).unwrap().triangle_index()
+    }
+
+    /// The triangle this one was subdivided from, or `None` at binning depth 0.
+    pub fn parent(self) -> Option<Self> {
+        let binning_depth = self.binning_depth().checked_sub(1)?;
+
+        Some(Self::new(binning_depth, self.triangle_index() / 4))
+    }
+
+    /// The four triangles this one is subdivided into, in index order. See
+    /// [`crate::Icosphere::subdivide`] for how they are laid out.
+    ///
+    /// Panics at [`MAX_TRIANGLE_ID_DEPTH`].
+    pub fn children(self) -> [Self; 4] {
+        let binning_depth = self.binning_depth() + 1;
+        let first_child = self.triangle_index() * 4;
+
+        [0, 1, 2, 3].map(|child| Self::new(binning_depth, first_child + child))
+    }
+
+    /// The triangle containing this one at the given binning depth, or `None` if it's deeper than
+    /// this one. A triangle is its own ancestor at its own binning depth.
+    pub fn ancestor(self, binning_depth: usize) -> Option<Self> {
+        let generations = self.binning_depth().checked_sub(binning_depth)?;
+
+        Some(Self::new(
+            binning_depth,
+            locate::ancestor_index(self.triangle_index(), generations),
+        ))
+    }
+
+    /// Whether `other` is this triangle or one of its descendants.
+    pub fn contains(self, other: Self) -> bool {
+        self.descendant_range().contains(&other.0)
+    }
+
+    /// The `u64` forms of this triangle and all of its descendants, down to
+    /// [`MAX_TRIANGLE_ID_DEPTH`]. No other ID is in this range.
+    pub fn descendant_range(self) -> RangeInclusive<u64> {
+        let below_marker = self.lowest_bit() - 1;
+
+        (self.0 - below_marker)..=(self.0 + below_marker)
+    }
+
+    /// The triangles sharing an edge with this one. See [`adjacency::edge_neighbors`].
+    pub fn edge_neighbors(self) -> [Self; 3] {
+        let binning_depth = self.binning_depth();
+
+        adjacency::edge_neighbors(self.triangle_index(), binning_depth)
+            .map(|triangle_index| Self::new(binning_depth, triangle_index))
+    }
+
+    /// The triangles sharing only a vertex with this one. See [`adjacency::vertex_neighbors`].
+    pub fn vertex_neighbors(self) -> Vec<Self> {
+        let binning_depth = self.binning_depth();
+
+        adjacency::vertex_neighbors(self.triangle_index(), binning_depth)
+            .into_iter()
+            .map(|triangle_index| Self::new(binning_depth, triangle_index))
+            .collect()
+    }
+
+    /// Positions of the triangle's corners, the same as in an icosphere with vertices placed by the
+    /// given rule.
+    pub fn corners(self, midpoint_rule: MidpointRule) -> [Vec3; 3] {
+        locate::triangle_corners(self.triangle_index(), self.binning_depth(), midpoint_rule)
+    }
+
+    /// The lowest set bit, which marks the binning depth.
+    fn lowest_bit(self) -> u64 {
+        self.0 & self.0.wrapping_neg()
+    }
+
+    fn marker_bit(binning_depth: usize) -> u64 {
+        1 << (2 * (MAX_TRIANGLE_ID_DEPTH - binning_depth))
+    }
+}
+
+impl From<TriangleId> for u64 {
+    fn from(id: TriangleId) -> Self {
+        id.to_u64()
+    }
+}
+
+impl TryFrom<u64> for TriangleId {
+    type Error = InvalidTriangleIdError;
+
+    fn try_from(id: u64) -> Result<Self, Self::Error> {
+        Self::from_u64(id).ok_or(InvalidTriangleIdError)
+    }
+}
+
+impl fmt::Display for TriangleId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", FACE_LETTERS[self.face()] as char)?;
+
+        for generation in (0..self.binning_depth()).rev() {
+            let child = locate::ancestor_index(self.triangle_index(), generation) % 4;
+            write!(f, "{child}")?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The string or `u64` a [`TriangleId`] was read from doesn't address a triangle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidTriangleIdError;
+
+impl fmt::Display for InvalidTriangleIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "not a valid triangle ID")
+    }
+}
+
+impl std::error::Error for InvalidTriangleIdError {}
+
+impl FromStr for TriangleId {
+    type Err = InvalidTriangleIdError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (&face_letter, children) = s.as_bytes().split_first().ok_or(InvalidTriangleIdError)?;
+
+        let face = FACE_LETTERS
+            .iter()
+            .position(|&letter| letter == face_letter)
+            .ok_or(InvalidTriangleIdError)?;
+
+        if children.len() > MAX_TRIANGLE_ID_DEPTH {
+            return Err(InvalidTriangleIdError);
+        }
+
+        let triangle_index =
+            children
+                .iter()
+                .try_fold(face, |parent_index, &child| match child {
+                    b'0'..=b'3' => Ok(parent_index * 4 + (child - b'0') as usize),
+                    _ => Err(InvalidTriangleIdError),
+                })?;
+
+        Ok(Self::new(children.len(), triangle_index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packing() {
+        let depth_0 = TriangleId::new(0, 2);
+        assert_eq!(depth_0.to_u64(), (2 << 1 | 1) << 58);
+
+        let id = TriangleId::new(4, 0b10_00_01_10_11);
+        assert_eq!(id.to_u64(), (0b10_00_01_10_11 << 1 | 1) << 50);
+        assert_eq!(
+            (id.binning_depth(), id.triangle_index()),
+            (4, 0b10_00_01_10_11)
+        );
+        assert_eq!(id.face(), 2);
+        assert_eq!(id.to_string(), "C0123");
+        assert_eq!("C0123".parse(), Ok(id));
+
+        let deepest = TriangleId::new(MAX_TRIANGLE_ID_DEPTH, triangle_count(29) - 1);
+        assert_eq!(deepest.to_u64() & 1, 1);
+        assert_eq!(TriangleId::from_u64(deepest.to_u64()), Some(deepest));
+    }
+
+    #[test]
+    fn round_trips() {
+        for binning_depth in [0, 1, 2, 3, 4, 15, MAX_TRIANGLE_ID_DEPTH] {
+            let count = triangle_count(binning_depth);
+
+            for triangle_index in [0, 1, count / 3, count - 1] {
+                let id = TriangleId::new(binning_depth, triangle_index);
+
+                assert_eq!(id.binning_depth(), binning_depth);
+                assert_eq!(id.triangle_index(), triangle_index);
+                assert_eq!(TriangleId::from_u64(id.to_u64()), Some(id));
+                assert_eq!(TriangleId::try_from(u64::from(id)), Ok(id));
+                assert_eq!(id.to_string().parse(), Ok(id));
+                assert_eq!(id.to_string().len(), binning_depth + 1);
+            }
+        }
+    }
+
+    #[test]
+    fn invalid_ids_are_rejected() {
+        // No marker bit
+        assert_eq!(TriangleId::from_u64(0), None);
+
+        // A marker bit at an odd position
+        assert_eq!(TriangleId::from_u64(1 << 1), None);
+        assert_eq!(TriangleId::from_u64(0b111 << 1), None);
+
+        // A marker bit above the shallowest depth
+        assert_eq!(TriangleId::from_u64(1 << 60), None);
+
+        // Face 20 and above don't exist
+        assert_eq!(TriangleId::from_u64((20 << 1 | 1) << 58), None);
+        assert!(TriangleId::from_u64((19 << 1 | 1) << 58).is_some());
+
+        for invalid in ["", "U", "a0", "C4", "C01x", &format!("A{}", "0".repeat(30))] {
+            assert_eq!(invalid.parse::<TriangleId>(), Err(InvalidTriangleIdError));
+        }
+
+        assert!(format!("A{}", "0".repeat(29)).parse::<TriangleId>().is_ok());
+    }
+
+    #[test]
+    #[should_panic(expected = "only go up to binning depth")]
+    fn new_rejects_depths_past_the_maximum() {
+        TriangleId::new(MAX_TRIANGLE_ID_DEPTH + 1, 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "only go up to binning depth")]
+    fn children_panic_at_the_maximum_depth() {
+        TriangleId::new(MAX_TRIANGLE_ID_DEPTH, 0).children();
+    }
+
+    #[test]
+    fn descendants_follow_the_parent() {
+        let parent: TriangleId = "H31".parse().unwrap();
+        let siblings: Vec<TriangleId> = ["H30", "H32", "H33"]
+            .iter()
+            .map(|s| s.parse().unwrap())
+            .collect();
+
+        assert!(parent.contains(parent));
+        assert_eq!(parent.parent().unwrap().to_string(), "H3");
+        assert!(parent.parent().unwrap().contains(parent));
+        assert!(!parent.contains(parent.parent().unwrap()));
+
+        for child in parent.children() {
+            assert_eq!(child.parent(), Some(parent));
+            assert!(parent.contains(child));
+            assert!(parent.descendant_range().contains(&child.to_u64()));
+
+            let deepest = TriangleId::new(
+                MAX_TRIANGLE_ID_DEPTH,
+                child.triangle_index() << (2 * (MAX_TRIANGLE_ID_DEPTH - child.binning_depth())),
+            );
+            assert!(parent.contains(deepest));
+            assert_eq!(deepest.ancestor(parent.binning_depth()), Some(parent));
+        }
+
+        for sibling in siblings {
+            assert!(!parent.contains(sibling));
+
+            for child in sibling.children() {
+                assert!(!parent.contains(child));
+            }
+
+            // Siblings are on one side of the whole range
+            let range = parent.descendant_range();
+            assert!(sibling.to_u64() < *range.start() || sibling.to_u64() > *range.end());
+        }
+
+        // The order follows the quadtree: a triangle comes between its descendants, and before the
+        // descendants of its next sibling
+        let [first, second, third, fourth] = parent.children();
+        assert!(first < second && second < third && third < fourth);
+        assert!(first < parent && parent < fourth);
+        assert!(fourth.children()[3] < "H32".parse().unwrap());
+    }
+
+    #[test]
+    fn descendant_range_at_the_depth_limits() {
+        let face = TriangleId::new(0, 7);
+        let range = face.descendant_range();
+
+        assert_eq!(
+            *range.start(),
+            TriangleId::new(MAX_TRIANGLE_ID_DEPTH, 7 << 58).to_u64()
+        );
+        assert_eq!(
+            *range.end(),
+            TriangleId::new(MAX_TRIANGLE_ID_DEPTH, (8 << 58) - 1).to_u64()
+        );
+
+        let deepest = TriangleId::new(MAX_TRIANGLE_ID_DEPTH, 12345);
+        assert_eq!(
+            deepest.descendant_range(),
+            deepest.to_u64()..=deepest.to_u64()
+        );
+        assert_eq!(deepest.ancestor(MAX_TRIANGLE_ID_DEPTH), Some(deepest));
+        assert_eq!(face.ancestor(1), None);
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::{HashMap, HashSet};
+
+use glam::{IVec3, Vec3};
+
+use crate::{Icosphere, IcosphereVertex, triangle_count, vertex_count};
+
+/// Vertices closer than this are reported as duplicates.
+const DUPLICATE_DISTANCE: f32 = 1e-6;
+
+/// How far the length of a vertex position can be from one.
+const UNIT_LENGTH_TOLERANCE: f32 = 1e-5;
+
+/// The problems found by [`validate`]. Every list is sorted, and empty if nothing is wrong.
+///
+/// A sparse icosphere that isn't completely generated is checked as far as it can be: counts and
+/// the Euler characteristic are only compared against the full icosphere once every triangle is
+/// generated, and vertices on the edge of the generated area may have fewer neighbors.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidationReport {
+    /// Whether every triangle at this binning depth is generated.
+    pub complete: bool,
+
+    /// The number of vertices in [`Icosphere::vertices`], and [`vertex_count`] at this binning depth.
+    pub vertex_count: usize,
+    pub expected_vertex_count: usize,
+
+    /// The number of generated triangles, and [`triangle_count`] at this binning depth.
+    pub triangle_count: usize,
+    pub expected_triangle_count: usize,
+
+    /// Used vertices minus edges plus triangles, which is 2 for a closed sphere.
+    pub euler_characteristic: isize,
+
+    /// Parent triangle indices with some, but not all four, of their children generated.
+    pub incomplete_chunks: Vec<usize>,
+
+    /// Edges, as sorted vertex index pairs, used by only one triangle.
+    pub boundary_edges: Vec<(u32, u32)>,
+
+    /// Edges, as sorted vertex index pairs, used more than once in the same direction, either by more
+    /// than two triangles or by two triangles wound in opposite directions.
+    pub non_manifold_edges: Vec<(u32, u32)>,
+
+    /// Triangles that are wound clockwise when viewed from outside, or degenerate.
+    pub inward_triangles: Vec<usize>,
+
+    /// Vertices not used by any triangle.
+    pub unused_vertices: Vec<usize>,
+
+    /// Pairs of vertices at the same position.
+    pub duplicate_vertices: Vec<(usize, usize)>,
+
+    /// Vertices whose position isn't on the unit sphere.
+    pub non_unit_vertices: Vec<usize>,
+
+    /// Vertices whose entry in [`Icosphere::neighbors`] doesn't match the edges of the triangles
+    /// using them.
+    pub neighbor_mismatches: Vec<usize>,
+
+    /// Vertices away from the edge of the generated area without five or six neighbors.
+    pub invalid_valences: Vec<usize>,
+}
+
+impl ValidationReport {
+    /// Whether no problem was found.
+    pub fn is_valid(&self) -> bool {
+        let counts_match = !self.complete
+            || (self.vertex_count == self.expected_vertex_count
+                && self.triangle_count == self.expected_triangle_count
+                && self.euler_characteristic == 2);
+
+        counts_match
+            && self.incomplete_chunks.is_empty()
+            && (!self.complete || self.boundary_edges.is_empty())
+            && self.non_manifold_edges.is_empty()
+            && self.inward_triangles.is_empty()
+            && self.unused_vertices.is_empty()
+            && self.duplicate_vertices.is_empty()
+            && self.non_unit_vertices.is_empty()
+            && self.neighbor_mismatches.is_empty()
+            && self.invalid_valences.is_empty()
+    }
+}
+
+/// Checks the invariants every icosphere should hold, and reports everything that doesn't.
+pub fn validate<T, S>(ico: &S) -> ValidationReport
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    let vertices = ico.vertices();
+    let triangle_indices = ico.allocated_triangle_indices();
+    let positions: Vec<Vec3> = vertices.iter().map(|vertex| vertex.position()).collect();
+
+    let mut directed_edges: HashMap<(u32, u32), usize> = HashMap::new();
+    let mut triangle_neighbors: HashMap<usize, HashSet<usize>> = HashMap::new();
+    let mut inward_triangles = Vec::new();
+
+    for &triangle_index in &triangle_indices {
+        let [a, b, c] = ico.triangle(triangle_index);
+
+        for (from, to) in [(a, b), (b, c), (c, a)] {
+            *directed_edges.entry((from, to)).or_default() += 1;
+
+            triangle_neighbors
+                .entry(from as usize)
+                .or_default()
+                .insert(to as usize);
+            triangle_neighbors
+                .entry(to as usize)
+                .or_default()
+                .insert(from as usize);
+        }
+
+        let [a, b, c] = [a, b, c].map(|i| positions[i as usize]);
+        if (b - a).cross(c - a).dot(a + b + c) <= 0.0 {
+            inward_triangles.push(triangle_index);
+        }
+    }
+
+    let mut boundary_edges = Vec::new();
+    let mut non_manifold_edges = Vec::new();
+    let mut edge_count = 0;
+
+    for (&(from, to), &count) in &directed_edges {
+        let sorted = (from.min(to), from.max(to));
+
+        if count > 1 {
+            non_manifold_edges.push(sorted);
+        }
+
+        // Count every undirected edge once, from its smaller vertex if both directions are used
+        if !directed_edges.contains_key(&(to, from)) {
+            boundary_edges.push(sorted);
+            edge_count += 1;
+        } else if from < to {
+            edge_count += 1;
+        }
+    }
+
+    non_manifold_edges.sort_unstable();
+    non_manifold_edges.dedup();
+    boundary_edges.sort_unstable();
+    boundary_edges.dedup();
+
+    let boundary_vertices: HashSet<usize> = boundary_edges
+        .iter()
+        .flat_map(|&(from, to)| [from as usize, to as usize])
+        .collect();
+
+    let unused_vertices = (0..vertices.len())
+        .filter(|vertex_index| !triangle_neighbors.contains_key(vertex_index))
+        .collect();
+
+    let no_neighbors = HashSet::new();
+    let mut neighbor_mismatches: Vec<usize> = (0..vertices.len())
+        .filter(|vertex_index| {
+            ico.neighbors().get(vertex_index).unwrap_or(&no_neighbors)
+                != triangle_neighbors
+                    .get(vertex_index)
+                    .unwrap_or(&no_neighbors)
+        })
+        .collect();
+
+    // Entries for vertices that don't exist
+    neighbor_mismatches.extend(
+        ico.neighbors()
+            .keys()
+            .filter(|&&vertex_index| vertex_index >= vertices.len()),
+    );
+    neighbor_mismatches.sort_unstable();
+
+    let mut invalid_valences: Vec<usize> = triangle_neighbors
+        .iter()
+        .filter(|(vertex_index, neighbors)| {
+            !boundary_vertices.contains(vertex_index) && !(5..=6).contains(&neighbors.len())
+        })
+        .map(|(&vertex_index, _)| vertex_index)
+        .collect();
+    invalid_valences.sort_unstable();
+
+    let non_unit_vertices = positions
+        .iter()
+        .enumerate()
+        .filter(|(_, position)| (position.length() - 1.0).abs() > UNIT_LENGTH_TOLERANCE)
+        .map(|(vertex_index, _)| vertex_index)
+        .collect();
+
+    let complete = triangle_indices.len() == ico.total_triangle_count();
+    let euler_characteristic =
+        triangle_neighbors.len() as isize - edge_count as isize + triangle_indices.len() as isize;
+
+    ValidationReport {
+        complete,
+        vertex_count: vertices.len(),
+        expected_vertex_count: vertex_count(ico.binning_depth()),
+        triangle_count: triangle_indices.len(),
+        expected_triangle_count: triangle_count(ico.binning_depth()),
+        euler_characteristic,
+        incomplete_chunks: incomplete_chunks(ico, &triangle_indices),
+        boundary_edges,
+        non_manifold_edges,
+        inward_triangles,
+        unused_vertices,
+        duplicate_vertices: duplicate_vertices(&positions),
+        non_unit_vertices,
+        neighbor_mismatches,
+        invalid_valences,
+    }
+}
+
+/// Parents with only some of their children generated. The regular icosahedron has no parents.
+fn incomplete_chunks<T, S>(ico: &S, triangle_indices: &[usize]) -> Vec<usize>
+where
+    T: IcosphereVertex,
+    S: Icosphere<T> + ?Sized,
+{
+    if ico.binning_depth() == 0 {
+        return Vec::new();
+    }
+
+    let mut parents: Vec<usize> = triangle_indices
+        .iter()
+        .map(|triangle_index| triangle_index / 4)
+        .filter(|parent_index| (0..4).any(|child| !ico.contains_triangle(parent_index * 4 + child)))
+        .collect();
+
+    parents.dedup();
+    parents
+}
+
+/// Pairs of positions closer than [`DUPLICATE_DISTANCE`], found by bucketing positions into cells of
+/// that size and comparing each with the neighboring cells.
+fn duplicate_vertices(positions: &[Vec3]) -> Vec<(usize, usize)> {
+    let cell = |position: Vec3| (position / DUPLICATE_DISTANCE).floor().as_ivec3();
+
+    let mut cells: HashMap<IVec3, Vec<usize>> = HashMap::new();
+    for (vertex_index, &position) in positions.iter().enumerate() {
+        cells.entry(cell(position)).or_default().push(vertex_index);
+    }
+
+    let mut duplicates = Vec::new();
+
+    for (vertex_index, &position) in positions.iter().enumerate() {
+        let center = cell(position);
+
+        for offset in (-1..=1)
+            .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))))
+        {
+            for &other in cells.get(&(center + offset)).into_iter().flatten() {
+                if other > vertex_index && positions[other].distance(position) < DUPLICATE_DISTANCE
+                {
+                    duplicates.push((vertex_index, other));
+                }
+            }
+        }
+    }
+
+    duplicates.sort_unstable();
+    duplicates
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{SparseIcosphere, StaticIcosphere};
+
+    fn edges_of(triangle: [u32; 3]) -> Vec<(u32, u32)> {
+        let [a, b, c] = triangle;
+        let mut edges: Vec<(u32, u32)> = [(a, b), (b, c), (c, a)]
+            .map(|(from, to)| (from.min(to), from.max(to)))
+            .to_vec();
+        edges.sort_unstable();
+
+        edges
+    }
+
+    #[test]
+    fn generated_icospheres_are_valid() {
+        for binning_depth in 0..4 {
+            let report = validate(&StaticIcosphere::<Vec3>::nth(binning_depth));
+
+            assert!(report.is_valid(), "{report:?}");
+            assert!(report.complete);
+            assert_eq!(report.euler_characteristic, 2);
+        }
+
+        let previous = SparseIcosphere::<Vec3>::filled(1);
+        let mut ico = SparseIcosphere::empty(2);
+        ico.subdivide_chunk(&previous, 3);
+
+        let report = validate(&ico);
+        assert!(report.is_valid(), "{report:?}");
+        assert!(!report.complete);
+        assert_eq!(report.triangle_count, 4);
+        assert_eq!(report.boundary_edges.len(), 6);
+    }
+
+    #[test]
+    fn flipped_triangle() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(2);
+        let [a, b, c] = ico.triangles[5];
+        ico.triangles[5] = [a, c, b];
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert_eq!(report.inward_triangles, [5]);
+
+        // Each edge is now used in the same direction by both triangles sharing it
+        assert_eq!(report.non_manifold_edges, edges_of([a, b, c]));
+        assert_eq!(report.boundary_edges, edges_of([a, b, c]));
+        assert!(report.neighbor_mismatches.is_empty());
+    }
+
+    #[test]
+    fn duplicated_vertex() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(1);
+        let duplicate = ico.vertices.len();
+        ico.vertices.push(ico.vertices[4]);
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert_eq!(report.duplicate_vertices, [(4, duplicate)]);
+        assert_eq!(report.unused_vertices, [duplicate]);
+        assert_eq!(report.vertex_count, report.expected_vertex_count + 1);
+        assert_eq!(report.euler_characteristic, 2);
+    }
+
+    #[test]
+    fn missing_neighbor() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(1);
+        let neighbor = *ico.neighbors[&3].iter().next().unwrap();
+        ico.neighbors.get_mut(&3).unwrap().remove(&neighbor);
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert_eq!(report.neighbor_mismatches, [3]);
+
+        // Neighbors of vertices that don't exist
+        let mut ico = StaticIcosphere::<Vec3>::nth(1);
+        ico.neighbors.insert(1000, HashSet::new());
+
+        assert_eq!(validate(&ico).neighbor_mismatches, [1000]);
+    }
+
+    #[test]
+    fn vertex_off_the_sphere() {
+        let mut ico = StaticIcosphere::<Vec3>::nth(1);
+        ico.vertices[7] *= 1.01;
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert_eq!(report.non_unit_vertices, [7]);
+    }
+
+    #[test]
+    fn missing_triangle() {
+        let mut ico = SparseIcosphere::<Vec3>::filled(2);
+        let removed = ico.triangles.remove(&22).unwrap();
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert!(!report.complete);
+        assert_eq!(report.triangle_count, report.expected_triangle_count - 1);
+        assert_eq!(report.incomplete_chunks, [5]);
+        assert_eq!(report.boundary_edges, edges_of(removed));
+    }
+
+    #[test]
+    fn merged_vertices() {
+        // Merging two vertices of the regular icosahedron gives the merged vertex more neighbors
+        let mut ico = StaticIcosphere::<Vec3>::nth(0);
+        let [kept, merged, _] = ico.triangles[0];
+
+        for triangle in &mut ico.triangles {
+            for vertex_index in triangle {
+                if *vertex_index == merged {
+                    *vertex_index = kept;
+                }
+            }
+        }
+
+        let report = validate(&ico);
+        assert!(!report.is_valid());
+        assert_eq!(report.unused_vertices, [merged as usize]);
+        assert!(report.invalid_valences.contains(&(kept as usize)));
+        assert!(report.non_manifold_edges.contains(&(kept, kept)));
+
+        // Both triangles that shared the merged edge collapsed
+        assert_eq!(report.inward_triangles.len(), 2);
+        assert!(report.inward_triangles.contains(&0));
+    }
+}